@@ -0,0 +1,484 @@
+use std::future::Future;
+use std::io::{Read, BufRead};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use ::{Generator, ReadWith, ReadPolicy};
+
+/// An asynchronous source of chunks for an
+/// [`AsyncReadWith`](struct.AsyncReadWith.html).
+///
+/// This is implemented for every
+/// `FnMut(&mut Context) -> Poll<io::Result<Option<S>>>`, through
+/// [`FutureFn`](struct.FutureFn.html) for functions that return futures
+/// and, with the `futures-core` feature, through
+/// [`FromStream`](struct.FromStream.html) and
+/// [`FromTryStream`](struct.FromTryStream.html) for streams.
+pub trait AsyncGenerator<S>
+{
+	/// Attempt to produce the next chunk, `None` at the end of the data.
+	fn poll_generate(&mut self, cx: &mut Context)
+		-> Poll<std::io::Result<Option<S>>>;
+}
+
+impl<F, S> AsyncGenerator<S> for F
+	where F: FnMut(&mut Context) -> Poll<std::io::Result<Option<S>>>
+{
+	fn poll_generate(&mut self, cx: &mut Context)
+		-> Poll<std::io::Result<Option<S>>>
+	{
+		self(cx)
+	}
+}
+
+/// Wraps a function that returns a future of the next chunk
+///
+/// Created by [`AsyncReadWith::from_future_fn`](struct.AsyncReadWith.html#method.from_future_fn).
+pub struct FutureFn<F, Fut>
+{
+	f: F,
+	running: Option<Pin<Box<Fut>>>,
+}
+
+impl<F, Fut, S> AsyncGenerator<S> for FutureFn<F, Fut>
+	where F: FnMut() -> Fut,
+	Fut: Future<Output=std::io::Result<Option<S>>>
+{
+	fn poll_generate(&mut self, cx: &mut Context)
+		-> Poll<std::io::Result<Option<S>>>
+	{
+		let f = &mut self.f;
+		let running = self.running.get_or_insert_with(|| Box::pin(f()));
+		let r = running.as_mut().poll(cx);
+		if r.is_ready()
+			{ self.running = None; }
+		r
+	}
+}
+
+/// Wraps a `Stream` of chunks
+///
+/// Created by [`AsyncReadWith::from_stream`](struct.AsyncReadWith.html#method.from_stream).
+#[cfg(feature = "futures-core")]
+pub struct FromStream<St>(Pin<Box<St>>);
+
+#[cfg(feature = "futures-core")]
+impl<St, S> AsyncGenerator<S> for FromStream<St>
+	where St: futures_core::Stream<Item=S>
+{
+	fn poll_generate(&mut self, cx: &mut Context)
+		-> Poll<std::io::Result<Option<S>>>
+	{
+		self.0.as_mut().poll_next(cx).map(Ok)
+	}
+}
+
+/// Wraps a `Stream` of `Result`s of chunks
+///
+/// Created by [`AsyncReadWith::from_try_stream`](struct.AsyncReadWith.html#method.from_try_stream).
+#[cfg(feature = "futures-core")]
+pub struct FromTryStream<St>(Pin<Box<St>>);
+
+#[cfg(feature = "futures-core")]
+impl<St, S, E> AsyncGenerator<S> for FromTryStream<St>
+	where St: futures_core::Stream<Item=Result<S, E>>,
+	E: Into<std::io::Error>
+{
+	fn poll_generate(&mut self, cx: &mut Context)
+		-> Poll<std::io::Result<Option<S>>>
+	{
+		self.0.as_mut().poll_next(cx)
+			.map(|c| c.transpose().map_err(Into::into))
+	}
+}
+
+/// Gives a `ReadWith` the chunks of an `AsyncGenerator`, with
+/// `Pending` as an error of kind `WouldBlock`
+struct Polled<F>
+{
+	f: F,
+	waker: Option<Waker>,
+	pending: bool,
+}
+
+impl<F, S> Generator<S> for Polled<F>
+	where F: AsyncGenerator<S>
+{
+	fn generate(&mut self) -> std::io::Result<Option<S>>
+	{
+		let waker = self.waker.as_ref()
+			.expect("only called while polled");
+		match self.f.poll_generate(&mut Context::from_waker(waker))
+		{
+			Poll::Ready(r) => r,
+			Poll::Pending =>
+			{
+				self.pending = true;
+				Err(std::io::Error::new(std::io::ErrorKind::WouldBlock, "pending"))
+			},
+		}
+	}
+}
+
+/// The asynchronous counterpart of [`ReadWith`](struct.ReadWith.html)
+///
+/// It implements `futures_io::AsyncRead` and `AsyncBufRead` with the
+/// `futures-io` feature, and tokio's with the `tokio` feature. It reads
+/// as a `ReadWith` with [`ReadPolicy::UntilWouldBlock`](enum.ReadPolicy.html)
+/// does, returning `Poll::Pending` when the generator does and
+/// no bytes could be read.
+///
+/// Example:
+///
+/// ```rust
+/// use std::pin::Pin;
+/// use std::task::{Context, Poll, Waker};
+/// let mut many_strings = vec!["one", "two", "three"].into_iter();
+/// let mut r = read_with::AsyncReadWith::new(
+///     |_: &mut Context| Poll::Ready(Ok(many_strings.next()))
+/// );
+/// let mut cx = Context::from_waker(Waker::noop());
+/// let mut buf = [0u8; 16];
+/// match Pin::new(&mut r).poll_read(&mut cx, &mut buf)
+/// {
+///     Poll::Ready(Ok(n)) => assert_eq!(&buf[..n], b"onetwothree"),
+///     _ => panic!(),
+/// }
+/// ```
+pub struct AsyncReadWith<F, S>
+	where F: AsyncGenerator<S>,
+	S: AsRef<[u8]>
+{
+	inner: ReadWith<Polled<F>, S>,
+}
+
+impl<F, S> AsyncReadWith<F, S>
+	where F: AsyncGenerator<S>,
+	S: AsRef<[u8]>
+{
+	/// Create an object that will read from the given `AsyncGenerator`.
+	pub fn new(f: F) -> Self
+	{
+		let polled = Polled { f, waker: None, pending: false };
+		AsyncReadWith
+		{
+			inner: ReadWith::with_generator(polled)
+				.read_policy(ReadPolicy::UntilWouldBlock),
+		}
+	}
+
+	/// Lend `cx` to the generator for one call on the reader,
+	/// turning its `Pending` back into `Poll::Pending`.
+	fn poll_with<T>(&mut self, cx: &mut Context,
+		call: impl FnOnce(&mut ReadWith<Polled<F>, S>) -> std::io::Result<T>)
+		-> Poll<std::io::Result<T>>
+	{
+		{
+			let polled = &mut self.inner.f;
+			match polled.waker
+			{
+				Some(ref w) if w.will_wake(cx.waker()) => {},
+				_ => polled.waker = Some(cx.waker().clone()),
+			}
+			polled.pending = false;
+		}
+		let r = call(&mut self.inner);
+		match r
+		{
+			Err(ref e) if self.inner.f.pending
+				&& e.kind() == std::io::ErrorKind::WouldBlock
+				=> Poll::Pending,
+			r => Poll::Ready(r),
+		}
+	}
+}
+
+impl<F, S> AsyncReadWith<F, S>
+	where F: AsyncGenerator<S> + Unpin,
+	S: AsRef<[u8]> + Unpin
+{
+	/// Attempt to read into `buf`, as `AsyncRead::poll_read` does.
+	///
+	/// Returns `Poll::Pending` only if no bytes could be copied.
+	pub fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buf: &mut [u8])
+		-> Poll<std::io::Result<usize>>
+	{
+		self.get_mut().poll_with(cx, |r| r.read(buf))
+	}
+
+	/// Attempt to return the rest of the current chunk, as
+	/// `AsyncBufRead::poll_fill_buf` does.
+	pub fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context)
+		-> Poll<std::io::Result<&[u8]>>
+	{
+		let this = self.get_mut();
+		match this.poll_with(cx, |r| r.fill_buf().map(|_| ()))
+		{
+			Poll::Ready(Ok(())) => Poll::Ready(Ok(this.inner.buffered())),
+			Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+			Poll::Pending => Poll::Pending,
+		}
+	}
+
+	/// Mark `amt` bytes returned by `poll_fill_buf` as read.
+	pub fn consume(self: Pin<&mut Self>, amt: usize)
+	{
+		self.get_mut().inner.consume(amt);
+	}
+}
+
+#[cfg(feature = "futures-io")]
+impl<F, S> futures_io::AsyncRead for AsyncReadWith<F, S>
+	where F: AsyncGenerator<S> + Unpin,
+	S: AsRef<[u8]> + Unpin
+{
+	fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buf: &mut [u8])
+		-> Poll<std::io::Result<usize>>
+	{
+		AsyncReadWith::poll_read(self, cx, buf)
+	}
+}
+
+#[cfg(feature = "futures-io")]
+impl<F, S> futures_io::AsyncBufRead for AsyncReadWith<F, S>
+	where F: AsyncGenerator<S> + Unpin,
+	S: AsRef<[u8]> + Unpin
+{
+	fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context)
+		-> Poll<std::io::Result<&[u8]>>
+	{
+		AsyncReadWith::poll_fill_buf(self, cx)
+	}
+
+	fn consume(self: Pin<&mut Self>, amt: usize)
+	{
+		AsyncReadWith::consume(self, amt)
+	}
+}
+
+#[cfg(feature = "tokio")]
+impl<F, S> tokio::io::AsyncRead for AsyncReadWith<F, S>
+	where F: AsyncGenerator<S> + Unpin,
+	S: AsRef<[u8]> + Unpin
+{
+	fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buf: &mut tokio::io::ReadBuf)
+		-> Poll<std::io::Result<()>>
+	{
+		let n = match AsyncReadWith::poll_read(self, cx, buf.initialize_unfilled())
+		{
+			Poll::Ready(Ok(n)) => n,
+			Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+			Poll::Pending => return Poll::Pending,
+		};
+		buf.advance(n);
+		Poll::Ready(Ok(()))
+	}
+}
+
+#[cfg(feature = "tokio")]
+impl<F, S> tokio::io::AsyncBufRead for AsyncReadWith<F, S>
+	where F: AsyncGenerator<S> + Unpin,
+	S: AsRef<[u8]> + Unpin
+{
+	fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context)
+		-> Poll<std::io::Result<&[u8]>>
+	{
+		AsyncReadWith::poll_fill_buf(self, cx)
+	}
+
+	fn consume(self: Pin<&mut Self>, amt: usize)
+	{
+		AsyncReadWith::consume(self, amt)
+	}
+}
+
+impl<F, Fut, S> AsyncReadWith<FutureFn<F, Fut>, S>
+	where F: FnMut() -> Fut,
+	Fut: Future<Output=std::io::Result<Option<S>>>,
+	S: AsRef<[u8]>
+{
+	/// Create an object that will read from the futures returned by `f`.
+	///
+	/// `f` is called again once its previous future has completed,
+	/// until one of them resolves to `Ok(None)`.
+	pub fn from_future_fn(f: F) -> Self
+	{
+		AsyncReadWith::new(FutureFn { f, running: None })
+	}
+}
+
+#[cfg(feature = "futures-core")]
+impl<St, S> AsyncReadWith<FromStream<St>, S>
+	where St: futures_core::Stream<Item=S>,
+	S: AsRef<[u8]>
+{
+	/// Create an object that will read each chunk from a `Stream`,
+	/// such as one of `Bytes`, until it ends.
+	pub fn from_stream(stream: St) -> Self
+	{
+		AsyncReadWith::new(FromStream(Box::pin(stream)))
+	}
+}
+
+#[cfg(feature = "futures-core")]
+impl<St, S, E> AsyncReadWith<FromTryStream<St>, S>
+	where St: futures_core::Stream<Item=Result<S, E>>,
+	E: Into<std::io::Error>,
+	S: AsRef<[u8]>
+{
+	/// Create an object that will read each chunk from a `Stream`
+	/// of `Result`s, such as `io::Result<Vec<u8>>`, until it ends.
+	///
+	/// An error from the stream is returned as `ReadWith::try_new`
+	/// returns an error from its function: after the bytes
+	/// already copied by that call.
+	pub fn from_try_stream(stream: St) -> Self
+	{
+		AsyncReadWith::new(FromTryStream(Box::pin(stream)))
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::AsyncReadWith;
+	use std::pin::Pin;
+	use std::task::{Context, Poll, Waker};
+
+	#[test]
+	fn pending()
+	{
+		let mut polls = 0;
+		let mut r = AsyncReadWith::new(
+			|_: &mut Context|
+			{
+				polls += 1;
+				match polls
+				{
+					1 => Poll::Ready(Ok(Some("one"))),
+					2 => Poll::Pending,
+					3 => Poll::Ready(Ok(Some("two"))),
+					4 => Poll::Pending,
+					_ => Poll::Ready(Ok(None)),
+				}
+			}
+		);
+		let mut cx = Context::from_waker(Waker::noop());
+		let mut buf = [0u8; 3];
+		assert_eq!(Pin::new(&mut r).poll_read(&mut cx, &mut buf).map(|r| r.unwrap()), Poll::Ready(3));
+		assert_eq!(&buf[..3], b"one");
+		assert_eq!(Pin::new(&mut r).poll_read(&mut cx, &mut buf).map(|r| r.unwrap()), Poll::Pending);
+		assert_eq!(Pin::new(&mut r).poll_read(&mut cx, &mut buf).map(|r| r.unwrap()), Poll::Ready(3));
+		assert_eq!(&buf[..3], b"two");
+		assert_eq!(Pin::new(&mut r).poll_read(&mut cx, &mut buf).map(|r| r.unwrap()), Poll::Pending);
+		assert_eq!(Pin::new(&mut r).poll_read(&mut cx, &mut buf).map(|r| r.unwrap()), Poll::Ready(0));
+	}
+
+	#[test]
+	fn futures()
+	{
+		let mut many_strings = vec!["one", "two", "three"].into_iter();
+		let mut r = AsyncReadWith::from_future_fn(
+			|| ::std::future::ready(Ok(many_strings.next()))
+		);
+		let mut cx = Context::from_waker(Waker::noop());
+		let mut output = vec!();
+		let mut buf = [0u8; 4];
+		loop
+		{
+			match Pin::new(&mut r).poll_read(&mut cx, &mut buf)
+			{
+				Poll::Ready(Ok(0)) => break,
+				Poll::Ready(Ok(n)) => output.extend_from_slice(&buf[..n]),
+				_ => panic!(),
+			}
+		}
+		assert_eq!("onetwothree", ::std::str::from_utf8(&output).unwrap());
+	}
+
+	/// Yields `Pending` before each chunk, waking the task at once.
+	#[cfg(any(feature = "futures-io", feature = "tokio"))]
+	fn every_other<'a>(chunks: Vec<&'a str>)
+		-> impl FnMut(&mut Context) -> Poll<::std::io::Result<Option<&'a str>>>
+	{
+		let mut chunks = chunks.into_iter();
+		let mut ready = false;
+		move |cx: &mut Context|
+		{
+			ready = !ready;
+			if !ready
+			{
+				cx.waker().wake_by_ref();
+				return Poll::Pending;
+			}
+			Poll::Ready(Ok(chunks.next()))
+		}
+	}
+
+	#[cfg(feature = "futures-io")]
+	#[test]
+	fn futures_io()
+	{
+		use futures::executor::block_on;
+		use futures::io::{AsyncReadExt, AsyncBufReadExt};
+		let mut r = AsyncReadWith::new(every_other(vec!["one\n", "tw", "o\nthree"]));
+		let mut line = String::new();
+		assert_eq!(block_on(r.read_line(&mut line)).unwrap(), 4);
+		assert_eq!(line, "one\n");
+		let mut rest = vec!();
+		assert_eq!(block_on(r.read_to_end(&mut rest)).unwrap(), 9);
+		assert_eq!(rest, b"two\nthree");
+	}
+
+	#[cfg(feature = "tokio")]
+	#[test]
+	fn tokio()
+	{
+		use futures::executor::block_on;
+		use tokio::io::{AsyncReadExt, AsyncBufReadExt};
+		let mut r = AsyncReadWith::new(every_other(vec!["one\n", "tw", "o\nthree"]));
+		let mut line = String::new();
+		assert_eq!(block_on(r.read_line(&mut line)).unwrap(), 4);
+		assert_eq!(line, "one\n");
+		let mut rest = vec!();
+		assert_eq!(block_on(r.read_to_end(&mut rest)).unwrap(), 9);
+		assert_eq!(rest, b"two\nthree");
+	}
+
+	#[cfg(all(feature = "futures-core", feature = "futures-io"))]
+	#[test]
+	fn streams()
+	{
+		use futures::channel::mpsc;
+		use futures::executor::block_on;
+		use futures::io::AsyncReadExt;
+		use futures::stream;
+		use std::io::{Error, ErrorKind};
+
+		let (tx, rx) = mpsc::unbounded();
+		let sender = ::std::thread::spawn(
+			move ||
+			{
+				for c in ["one", "", "two", "three"]
+				{
+					::std::thread::sleep(::std::time::Duration::from_millis(2));
+					tx.unbounded_send(c.as_bytes().to_vec()).unwrap();
+				}
+			}
+		);
+		let mut r = AsyncReadWith::from_stream(rx);
+		let mut s = String::new();
+		assert_eq!(block_on(r.read_to_string(&mut s)).unwrap(), 11);
+		assert_eq!(s, "onetwothree");
+		sender.join().unwrap();
+
+		let mut r = AsyncReadWith::from_try_stream(
+			stream::iter(vec![Ok("ab"), Err(Error::other("failed")), Ok("cd")])
+		);
+		let mut buf = [0u8; 8];
+		assert_eq!(block_on(r.read(&mut buf)).unwrap(), 2);
+		assert_eq!(block_on(r.read(&mut buf)).unwrap_err().kind(), ErrorKind::Other);
+		assert_eq!(block_on(r.read(&mut buf)).unwrap(), 2);
+		assert_eq!(&buf[..2], b"cd");
+		assert_eq!(block_on(r.read(&mut buf)).unwrap(), 0);
+	}
+}
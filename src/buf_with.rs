@@ -0,0 +1,166 @@
+use std::io::BufRead;
+use ::{Generator, ReadWith};
+
+/// A `bytes::Buf` of the data of a `ReadWith` of known length
+///
+/// Created by [`ReadWith::into_buf`](struct.ReadWith.html#method.into_buf).
+/// `Buf` can't call the function from `chunk`, so the next chunk is
+/// generated as soon as the current one has been advanced past, unlike
+/// with `Read`; the function is not called again after the last byte.
+///
+/// `Buf` can't fail either. If the function fails, or ends before
+/// producing the length, `remaining` drops to zero and the error is
+/// kept by [`error`](#method.error) and returned by the next read
+/// after [`into_inner`](#method.into_inner).
+pub struct BufWith<F, S>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	inner: ReadWith<F, S>,
+	error: Option<std::io::Error>,
+}
+
+impl<F, S> ReadWith<F, S>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	/// Read the data as a `bytes::Buf`, promising that the function
+	/// produces exactly `len` bytes, as with [`total_len`](#method.total_len).
+	///
+	/// The first chunk is generated at once.
+	///
+	/// ```rust
+	/// extern crate bytes;
+	/// extern crate read_with;
+	/// use bytes::Buf;
+	/// let mut b = read_with::ReadWith::from_iter(vec!["ab", "cd"]).into_buf(4);
+	/// assert_eq!(b.remaining(), 4);
+	/// assert_eq!(b.copy_to_bytes(4), "abcd");
+	/// assert!(b.error().is_none());
+	/// ```
+	pub fn into_buf(self, len: u64) -> BufWith<F, S>
+	{
+		let mut b = BufWith
+		{
+			inner: self.total_len(len),
+			error: None,
+		};
+		b.fill();
+		b
+	}
+}
+
+impl<F, S> BufWith<F, S>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	/// The error that ended the data early, if there was one.
+	pub fn error(&self) -> Option<&std::io::Error>
+	{
+		self.error.as_ref()
+	}
+
+	/// Stop reading as a `Buf`, returning the reader.
+	pub fn into_inner(mut self) -> ReadWith<F, S>
+	{
+		if let Some(e) = self.error.take()
+			{ self.inner.error = Some(e); }
+		self.inner
+	}
+
+	/// Make sure that `chunk` isn't empty while bytes remain.
+	fn fill(&mut self)
+	{
+		if self.inner.buffered().is_empty() && bytes::Buf::has_remaining(self)
+		{
+			if let Err(e) = self.inner.fill_buf()
+				{ self.error = Some(e); }
+		}
+	}
+}
+
+impl<F, S> bytes::Buf for BufWith<F, S>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	fn remaining(&self) -> usize
+	{
+		if self.error.is_some()
+			{ return 0; }
+		self.inner.size_hint().0.min(usize::MAX as u64) as usize
+	}
+
+	fn chunk(&self) -> &[u8]
+	{
+		if self.error.is_some()
+			{ return &[]; }
+		self.inner.buffered()
+	}
+
+	/// Panics if `cnt` is more than `remaining`.
+	fn advance(&mut self, mut cnt: usize)
+	{
+		assert!(cnt <= self.remaining(), "advanced past the end of the data");
+		while cnt > 0 && self.error.is_none()
+		{
+			let n = self.inner.buffered().len().min(cnt);
+			self.inner.consume(n);
+			cnt -= n;
+			self.fill();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::ReadWith;
+	use bytes::Buf;
+	use std::io::{Read, Error, ErrorKind};
+
+	#[test]
+	fn exact()
+	{
+		let mut calls = 0;
+		let mut chunks = vec!["ab", "", "cd", "e"].into_iter();
+		let mut b = ReadWith::new(|| { calls += 1; chunks.next() }).into_buf(5);
+		assert!(b.has_remaining());
+		assert_eq!(b.chunk(), b"ab");
+		assert_eq!(b.get_u8(), b'a');
+		assert_eq!(b.copy_to_bytes(b.remaining()), "bcde");
+		assert!(!b.has_remaining());
+		assert!(b.error().is_none());
+		drop(b);
+		// not called again for the end
+		assert_eq!(calls, 4);
+	}
+
+	#[test]
+	fn failures()
+	{
+		let mut calls = 0;
+		let mut b = ReadWith::try_new(
+			||
+			{
+				calls += 1;
+				match calls
+				{
+					1 => Ok(Some("ab")),
+					2 => Err(Error::other("failed")),
+					_ => Ok(Some("cd")),
+				}
+			}
+		).into_buf(4);
+		b.advance(2);
+		assert_eq!(b.remaining(), 0);
+		assert_eq!(b.error().unwrap().kind(), ErrorKind::Other);
+		let mut r = b.into_inner();
+		let mut buf = [0u8; 4];
+		assert_eq!(r.read(&mut buf).unwrap_err().kind(), ErrorKind::Other);
+		assert_eq!(r.read(&mut buf).unwrap(), 2);
+
+		let mut b = ReadWith::from_iter(vec!["ab", "cd"]).into_buf(5);
+		b.advance(4);
+		assert_eq!(b.error().unwrap().kind(), ErrorKind::UnexpectedEof);
+	}
+}
@@ -0,0 +1,326 @@
+use std::collections::VecDeque;
+use std::io::Read;
+use ::{Generator, ReadWith};
+
+/// A chunk from either of two generators
+///
+/// The chunk type of [`ReadWith::chain`](struct.ReadWith.html#method.chain)
+/// and [`ReadWith::interleave`](struct.ReadWith.html#method.interleave).
+pub enum Either<L, R>
+{
+	/// A chunk from the first generator
+	Left(L),
+	/// A chunk from the second generator
+	Right(R),
+}
+
+impl<L, R> AsRef<[u8]> for Either<L, R>
+	where L: AsRef<[u8]>,
+	R: AsRef<[u8]>
+{
+	fn as_ref(&self) -> &[u8]
+	{
+		match *self
+		{
+			Either::Left(ref l) => l.as_ref(),
+			Either::Right(ref r) => r.as_ref(),
+		}
+	}
+}
+
+/// A `Generator` of the chunks of one generator, then another
+///
+/// Created by [`ReadWith::chain`](struct.ReadWith.html#method.chain).
+pub struct Chain<A, B>
+{
+	a: Option<A>,
+	b: B,
+}
+
+impl<A, B, S, T> Generator<Either<S, T>> for Chain<A, B>
+	where A: Generator<S>,
+	B: Generator<T>
+{
+	fn generate(&mut self) -> std::io::Result<Option<Either<S, T>>>
+	{
+		if let Some(ref mut a) = self.a
+		{
+			if let Some(c) = a.generate()?
+				{ return Ok(Some(Either::Left(c))); }
+		}
+		self.a = None;
+		Ok(self.b.generate()?.map(Either::Right))
+	}
+}
+
+/// A `Generator` that takes a chunk from each of two generators in turn
+///
+/// Created by [`ReadWith::interleave`](struct.ReadWith.html#method.interleave).
+pub struct Interleave<A, B>
+{
+	a: Option<A>,
+	b: Option<B>,
+	b_next: bool,
+}
+
+impl<A, B, S, T> Generator<Either<S, T>> for Interleave<A, B>
+	where A: Generator<S>,
+	B: Generator<T>
+{
+	fn generate(&mut self) -> std::io::Result<Option<Either<S, T>>>
+	{
+		for _ in 0..2
+		{
+			if self.b_next
+			{
+				if let Some(ref mut b) = self.b
+				{
+					if let Some(c) = b.generate()?
+					{
+						self.b_next = self.a.is_none();
+						return Ok(Some(Either::Right(c)));
+					}
+				}
+				self.b = None;
+			}
+			else
+			{
+				if let Some(ref mut a) = self.a
+				{
+					if let Some(c) = a.generate()?
+					{
+						self.b_next = self.b.is_some();
+						return Ok(Some(Either::Left(c)));
+					}
+				}
+				self.a = None;
+			}
+			self.b_next = !self.b_next;
+		}
+		Ok(None)
+	}
+}
+
+/// A `Generator` of the chunks of each of a list of generators in turn
+///
+/// Created by [`ReadWith::concat`](struct.ReadWith.html#method.concat).
+pub struct Concat<'a, S>
+{
+	sources: VecDeque<Box<dyn Generator<S> + 'a>>,
+}
+
+impl<'a, S> Generator<S> for Concat<'a, S>
+{
+	fn generate(&mut self) -> std::io::Result<Option<S>>
+	{
+		while let Some(source) = self.sources.front_mut()
+		{
+			if let Some(c) = source.generate()?
+				{ return Ok(Some(c)); }
+			self.sources.pop_front();
+		}
+		Ok(None)
+	}
+}
+
+/// A `Generator` that takes one chunk from each of a list of
+/// generators in turn, until they have all ended
+///
+/// Created by [`ReadWith::round_robin`](struct.ReadWith.html#method.round_robin).
+pub struct RoundRobin<'a, S>
+{
+	sources: VecDeque<Box<dyn Generator<S> + 'a>>,
+}
+
+impl<'a, S> Generator<S> for RoundRobin<'a, S>
+{
+	fn generate(&mut self) -> std::io::Result<Option<S>>
+	{
+		while let Some(mut source) = self.sources.pop_front()
+		{
+			match source.generate()
+			{
+				Ok(Some(c)) =>
+				{
+					self.sources.push_back(source);
+					return Ok(Some(c));
+				},
+				Ok(None) => {},
+				Err(e) =>
+				{
+					// its turn is not over
+					self.sources.push_front(source);
+					return Err(e);
+				},
+			}
+		}
+		Ok(None)
+	}
+}
+
+/// A `Generator` of the data of a `Read`, in chunks
+/// of at most a given size
+///
+/// This lets a reader be combined with other generators, for
+/// example by [`ReadWith::concat`](struct.ReadWith.html#method.concat).
+pub struct ReadChunks<R>
+{
+	r: R,
+	size: usize,
+}
+
+impl<R> ReadChunks<R>
+	where R: Read
+{
+	/// Read `r` in chunks of at most `size` bytes.
+	///
+	/// Panics if `size` is zero.
+	pub fn new(r: R, size: usize) -> Self
+	{
+		assert!(size > 0, "chunk size must not be zero");
+		ReadChunks { r, size }
+	}
+}
+
+impl<R> Generator<Vec<u8>> for ReadChunks<R>
+	where R: Read
+{
+	fn generate(&mut self) -> std::io::Result<Option<Vec<u8>>>
+	{
+		let mut chunk = vec![0u8; self.size];
+		loop
+		{
+			match self.r.read(&mut chunk)
+			{
+				Ok(0) => return Ok(None),
+				Ok(n) =>
+				{
+					chunk.truncate(n);
+					return Ok(Some(chunk));
+				},
+				Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+				Err(e) => return Err(e),
+			}
+		}
+	}
+}
+
+impl<'a, S> ReadWith<Concat<'a, S>, S>
+	where S: AsRef<[u8]>
+{
+	/// Create an object that will read from each of the
+	/// generators in turn, until each one ends.
+	///
+	/// ```rust
+	/// use std::io::Read;
+	/// use read_with::{ReadWith, Generator, ReadChunks};
+	/// let mut header = Some(b"HEAD ".to_vec());
+	/// let mut footer = vec![b" FOOT".to_vec()].into_iter();
+	/// let sources: Vec<Box<dyn Generator<Vec<u8>>>> = vec!(
+	///     Box::new(move || header.take()),
+	///     Box::new(ReadChunks::new(&b"the body"[..], 4)),
+	///     Box::new(move || footer.next()),
+	/// );
+	/// let mut s = String::new();
+	/// ReadWith::concat(sources).read_to_string(&mut s).unwrap();
+	/// assert_eq!(s, "HEAD the body FOOT");
+	/// ```
+	pub fn concat(sources: Vec<Box<dyn Generator<S> + 'a>>) -> Self
+	{
+		ReadWith::with_generator(Concat { sources: sources.into() })
+	}
+}
+
+impl<'a, S> ReadWith<RoundRobin<'a, S>, S>
+	where S: AsRef<[u8]>
+{
+	/// Create an object that will read one chunk from each
+	/// generator in turn, leaving out those that have ended.
+	pub fn round_robin(sources: Vec<Box<dyn Generator<S> + 'a>>) -> Self
+	{
+		ReadWith::with_generator(RoundRobin { sources: sources.into() })
+	}
+}
+
+/// Like the other combinators, these are meant to be called
+/// before reading.
+impl<F, S> ReadWith<F, S>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	/// Read the chunks of `next` after these.
+	///
+	/// ```rust
+	/// use std::io::Read;
+	/// let mut body = vec![b"body".to_vec()].into_iter();
+	/// let mut s = String::new();
+	/// read_with::ReadWith::from_iter(vec!["head "])
+	///     .chain(move || body.next())
+	///     .read_to_string(&mut s)
+	///     .unwrap();
+	/// assert_eq!(s, "head body");
+	/// ```
+	pub fn chain<G, T>(self, next: G) -> ReadWith<Chain<F, G>, Either<S, T>>
+		where G: Generator<T>,
+		T: AsRef<[u8]>
+	{
+		self.replace_generator(|f| Chain { a: Some(f), b: next })
+	}
+
+	/// Read a chunk from these and then one from `other`, in turn.
+	///
+	/// Once either of them ends, the rest of the other is read.
+	pub fn interleave<G, T>(self, other: G) -> ReadWith<Interleave<F, G>, Either<S, T>>
+		where G: Generator<T>,
+		T: AsRef<[u8]>
+	{
+		self.replace_generator(|f| Interleave { a: Some(f), b: Some(other), b_next: false })
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::{ReadWith, Generator};
+	use std::io::{Read, Error, ErrorKind};
+
+	#[test]
+	fn interleave()
+	{
+		let mut s = String::new();
+		let mut b = vec!["1", "2", "3", "4"].into_iter();
+		ReadWith::from_iter(vec![b"a".to_vec(), b"b".to_vec()])
+			.interleave(move || b.next())
+			.read_to_string(&mut s)
+			.unwrap();
+		assert_eq!(s, "a1b234");
+
+		let mut calls = 0;
+		let failing = move ||
+		{
+			calls += 1;
+			match calls
+			{
+				1 => Ok(Some("x")),
+				2 => Err(Error::other("failed")),
+				3 => Ok(Some("y")),
+				_ => Ok(None),
+			}
+		};
+		let mut a = vec!["1", "2", "3"].into_iter();
+		let mut c = vec!["p", "q"].into_iter();
+		let sources: Vec<Box<dyn Generator<&str>>> = vec!(
+			Box::new(move || a.next()),
+			Box::new(::TryFn(failing)),
+			Box::new(move || c.next()),
+		);
+		let mut r = ReadWith::round_robin(sources);
+		let mut buf = [0u8; 16];
+		assert_eq!(r.read(&mut buf).unwrap(), 4);
+		assert_eq!(&buf[..4], b"1xp2");
+		assert_eq!(r.read(&mut buf).unwrap_err().kind(), ErrorKind::Other);
+		let mut s = String::new();
+		r.read_to_string(&mut s).unwrap();
+		assert_eq!(s, "yq3");
+	}
+}
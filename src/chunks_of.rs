@@ -0,0 +1,394 @@
+use std::io::Read;
+use ::LengthPrefix;
+
+/// How [`ChunksOf`](struct.ChunksOf.html) finds where each chunk ends
+///
+/// These match the [`Framing`](enum.Framing.html) modes, so that
+/// `ChunksOf` can undo `ReadWith::framed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split<'a>
+{
+	/// Chunks are separated by these bytes.
+	Delimiter(&'a [u8]),
+	/// Each chunk is followed by these bytes. An unterminated
+	/// last chunk is returned as it is.
+	Terminator(&'a [u8]),
+	/// Every chunk has this many bytes, except perhaps the last.
+	Size(usize),
+	/// Each chunk starts with its length.
+	LengthPrefix(LengthPrefix),
+	/// Each chunk is a netstring, `<length>:<chunk>,`.
+	Netstring,
+}
+
+/// An `Iterator` over the chunks of a `Read`
+///
+/// Example:
+///
+/// ```rust
+/// use read_with::{ChunksOf, Split};
+/// let lines: Vec<Vec<u8>> = ChunksOf::new(&b"one\ntwo\n"[..], Split::Terminator(b"\n"))
+///     .map(|c| c.unwrap())
+///     .collect();
+/// assert_eq!(lines, [b"one", b"two"]);
+/// ```
+pub struct ChunksOf<'a, R>
+	where R: Read
+{
+	r: R,
+	split: Split<'a>,
+	buf: Vec<u8>,
+	pos: usize,
+	after_delimiter: bool,
+	done: bool,
+}
+
+fn unexpected_eof() -> std::io::Error
+{
+	std::io::Error::new(
+		std::io::ErrorKind::UnexpectedEof,
+		"the data ended in the middle of a chunk",
+	)
+}
+
+fn invalid(msg: &'static str) -> std::io::Error
+{
+	std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+impl<'a, R> ChunksOf<'a, R>
+	where R: Read
+{
+	/// Split the data from `r` into chunks.
+	///
+	/// Panics if given an empty delimiter or terminator,
+	/// or `Split::Size(0)`.
+	pub fn new(r: R, split: Split<'a>) -> Self
+	{
+		match split
+		{
+			Split::Delimiter(d) | Split::Terminator(d) =>
+				assert!(!d.is_empty(), "delimiter must not be empty"),
+			Split::Size(n) =>
+				assert!(n > 0, "chunk size must not be zero"),
+			_ => {},
+		}
+
+		ChunksOf
+		{
+			r,
+			split,
+			buf: vec!(),
+			pos: 0,
+			after_delimiter: false,
+			done: false,
+		}
+	}
+
+	/// Read more data, returning false at its end.
+	fn fill(&mut self) -> std::io::Result<bool>
+	{
+		self.buf.drain(..self.pos);
+		self.pos = 0;
+		let start = self.buf.len();
+		self.buf.resize(start + 8192, 0);
+		loop
+		{
+			match self.r.read(&mut self.buf[start..])
+			{
+				Ok(n) =>
+				{
+					self.buf.truncate(start+n);
+					return Ok(n > 0);
+				},
+				Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+				Err(e) =>
+				{
+					self.buf.truncate(start);
+					return Err(e);
+				},
+			}
+		}
+	}
+
+	fn available(&self) -> usize
+	{
+		self.buf.len()-self.pos
+	}
+
+	/// Make at least `n` bytes available, returning false if
+	/// the data ends first.
+	fn ensure(&mut self, n: usize) -> std::io::Result<bool>
+	{
+		while self.available() < n
+		{
+			if !self.fill()?
+				{ return Ok(false); }
+		}
+		Ok(true)
+	}
+
+	fn take(&mut self, n: usize) -> Vec<u8>
+	{
+		let chunk = self.buf[self.pos..self.pos+n].to_vec();
+		self.pos += n;
+		chunk
+	}
+
+	fn until(&mut self, d: &[u8], delimiter: bool)
+		-> std::io::Result<Option<Vec<u8>>>
+	{
+		let mut searched = 0;
+		loop
+		{
+			let found = self.buf[self.pos+searched..]
+				.windows(d.len())
+				.position(|w| w == d);
+			if let Some(p) = found
+			{
+				let chunk = self.take(searched+p);
+				self.pos += d.len();
+				self.after_delimiter = delimiter;
+				return Ok(Some(chunk));
+			}
+			searched = (self.available()+1).saturating_sub(d.len());
+			if !self.fill()?
+				{ break; }
+		}
+
+		let rest = self.available();
+		if rest > 0 || self.after_delimiter
+		{
+			self.after_delimiter = false;
+			return Ok(Some(self.take(rest)));
+		}
+		Ok(None)
+	}
+
+	fn length(&mut self, prefix: LengthPrefix)
+		-> std::io::Result<Option<usize>>
+	{
+		let width = match prefix
+		{
+			LengthPrefix::U16Be | LengthPrefix::U16Le => 2,
+			LengthPrefix::U32Be | LengthPrefix::U32Le => 4,
+			LengthPrefix::Varint =>
+			{
+				let mut len = 0u64;
+				for i in 0..10
+				{
+					if !self.ensure(1)?
+					{
+						if i == 0
+							{ return Ok(None); }
+						return Err(unexpected_eof());
+					}
+					let b = self.buf[self.pos];
+					self.pos += 1;
+					// only the lowest bit of the tenth byte fits in 64 bits
+					if i == 9 && b > 1
+						{ return Err(invalid("chunk length is too large")); }
+					len |= ((b & 0x7f) as u64) << (7*i);
+					if b & 0x80 == 0
+					{
+						if len > usize::MAX as u64
+							{ return Err(invalid("chunk length is too large")); }
+						return Ok(Some(len as usize));
+					}
+				}
+				return Err(invalid("varint length is too long"));
+			},
+		};
+
+		if !self.ensure(width)?
+		{
+			if self.available() == 0
+				{ return Ok(None); }
+			return Err(unexpected_eof());
+		}
+		let h = &self.buf[self.pos..self.pos+width];
+		let len = match prefix
+		{
+			LengthPrefix::U16Be => u16::from_be_bytes([h[0], h[1]]) as usize,
+			LengthPrefix::U16Le => u16::from_le_bytes([h[0], h[1]]) as usize,
+			LengthPrefix::U32Be => u32::from_be_bytes([h[0], h[1], h[2], h[3]]) as usize,
+			LengthPrefix::U32Le => u32::from_le_bytes([h[0], h[1], h[2], h[3]]) as usize,
+			LengthPrefix::Varint => unreachable!(),
+		};
+		self.pos += width;
+		Ok(Some(len))
+	}
+
+	fn netstring_length(&mut self)
+		-> std::io::Result<Option<usize>>
+	{
+		let mut len = 0usize;
+		for i in 0..21
+		{
+			if !self.ensure(1)?
+			{
+				if i == 0
+					{ return Ok(None); }
+				return Err(unexpected_eof());
+			}
+			let b = self.buf[self.pos];
+			self.pos += 1;
+			match b
+			{
+				b':' if i > 0 => return Ok(Some(len)),
+				b'0' ..= b'9' =>
+				{
+					len = len.checked_mul(10)
+						.and_then(|l| l.checked_add((b - b'0') as usize))
+						.ok_or_else(|| invalid("netstring length is too large"))?;
+				},
+				_ => return Err(invalid("invalid netstring length")),
+			}
+		}
+		Err(invalid("netstring length is too long"))
+	}
+
+	fn read_chunk(&mut self)
+		-> std::io::Result<Option<Vec<u8>>>
+	{
+		match self.split
+		{
+			Split::Delimiter(d) => self.until(d, true),
+			Split::Terminator(t) => self.until(t, false),
+			Split::Size(n) =>
+			{
+				if !self.ensure(n)?
+				{
+					let rest = self.available();
+					if rest == 0
+						{ return Ok(None); }
+					return Ok(Some(self.take(rest)));
+				}
+				Ok(Some(self.take(n)))
+			},
+			Split::LengthPrefix(p) =>
+			{
+				let len = match self.length(p)?
+				{
+					Some(len) => len,
+					None => return Ok(None),
+				};
+				if !self.ensure(len)?
+					{ return Err(unexpected_eof()); }
+				Ok(Some(self.take(len)))
+			},
+			Split::Netstring =>
+			{
+				let len = match self.netstring_length()?
+				{
+					Some(len) => len,
+					None => return Ok(None),
+				};
+				let with_comma = len.checked_add(1)
+					.ok_or_else(|| invalid("netstring length is too large"))?;
+				if !self.ensure(with_comma)?
+					{ return Err(unexpected_eof()); }
+				if self.buf[self.pos+len] != b','
+					{ return Err(invalid("netstring does not end with a comma")); }
+				let chunk = self.take(len);
+				self.pos += 1;
+				Ok(Some(chunk))
+			},
+		}
+	}
+}
+
+impl<'a, R> Iterator for ChunksOf<'a, R>
+	where R: Read
+{
+	type Item = std::io::Result<Vec<u8>>;
+
+	/// Returns the next chunk; after an error or the end
+	/// of the data, returns `None`.
+	fn next(&mut self) -> Option<Self::Item>
+	{
+		if self.done
+			{ return None; }
+		match self.read_chunk()
+		{
+			Ok(Some(c)) => Some(Ok(c)),
+			Ok(None) =>
+			{
+				self.done = true;
+				None
+			},
+			Err(e) =>
+			{
+				self.done = true;
+				Some(Err(e))
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::{ChunksOf, Split, ReadWith, Framing, LengthPrefix, ReadPolicy};
+	use std::io::ErrorKind;
+
+	fn round_trip(chunks: &[&[u8]], framing: Framing, split: Split)
+	{
+		let mut many = chunks.iter().cloned();
+		// one byte at a time, to split chunks and their framing
+		let r = ReadWith::framed(|| many.next(), framing)
+			.read_policy(ReadPolicy::OneChunk);
+		let output: Vec<Vec<u8>> = ChunksOf::new(OneByte(r), split)
+			.map(|c| c.unwrap())
+			.collect();
+		assert_eq!(output, chunks);
+	}
+
+	struct OneByte<R>(R);
+
+	impl<R: ::std::io::Read> ::std::io::Read for OneByte<R>
+	{
+		fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize>
+		{
+			let n = buf.len().min(1);
+			self.0.read(&mut buf[..n])
+		}
+	}
+
+	#[test]
+	fn framing_round_trips()
+	{
+		let long = vec![b'x'; 300];
+		let chunks = [&b"one"[..], b"", b"tw,o", &long, b"three"];
+		round_trip(&chunks, Framing::Delimiter(b",,"), Split::Delimiter(b",,"));
+		round_trip(&chunks, Framing::Terminator(b"\r\n"), Split::Terminator(b"\r\n"));
+		round_trip(&chunks, Framing::Netstring, Split::Netstring);
+		for &p in &[LengthPrefix::U16Be, LengthPrefix::U16Le, LengthPrefix::U32Be, LengthPrefix::U32Le, LengthPrefix::Varint]
+			{ round_trip(&chunks, Framing::LengthPrefix(p), Split::LengthPrefix(p)); }
+		round_trip(&[b"a", b""], Framing::Delimiter(b","), Split::Delimiter(b","));
+		round_trip(&[], Framing::Delimiter(b","), Split::Delimiter(b","));
+	}
+
+	#[test]
+	fn sizes_and_errors()
+	{
+		let c: Vec<Vec<u8>> = ChunksOf::new(&b"abcdefg"[..], Split::Size(3))
+			.map(|c| c.unwrap())
+			.collect();
+		assert_eq!(c, [&b"abc"[..], b"def", b"g"]);
+
+		let mut c = ChunksOf::new(&b"\x00\x05abc"[..], Split::LengthPrefix(LengthPrefix::U16Be));
+		assert_eq!(c.next().unwrap().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+		assert!(c.next().is_none());
+
+		let mut c = ChunksOf::new(&b"3:abc;"[..], Split::Netstring);
+		assert_eq!(c.next().unwrap().unwrap_err().kind(), ErrorKind::InvalidData);
+		let max = format!("{}:abc,", usize::MAX);
+		let mut c = ChunksOf::new(max.as_bytes(), Split::Netstring);
+		assert_eq!(c.next().unwrap().unwrap_err().kind(), ErrorKind::InvalidData);
+
+		let mut c = ChunksOf::new(&b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x02"[..],
+			Split::LengthPrefix(LengthPrefix::Varint));
+		assert_eq!(c.next().unwrap().unwrap_err().kind(), ErrorKind::InvalidData);
+	}
+}
@@ -0,0 +1,401 @@
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+use std::io::Write;
+use std::marker::PhantomData;
+use ::{Generator, ReadWith};
+
+/// An incremental compressor, for
+/// [`ReadWith::compressed`](struct.ReadWith.html#method.compressed)
+///
+/// Each method appends its compressed output to `out`. With the `gzip`
+/// and `zstd` features, this is implemented by [`Gzip`](struct.Gzip.html)
+/// and [`Zstd`](struct.Zstd.html).
+pub trait Compressor
+{
+	/// Compress `input`; the output may be held back until later.
+	fn compress(&mut self, input: &[u8], out: &mut Vec<u8>)
+		-> std::io::Result<()>;
+	/// Output everything held back, so that what has been
+	/// output so far can be decompressed.
+	fn flush(&mut self, out: &mut Vec<u8>)
+		-> std::io::Result<()>;
+	/// Output the end of the compressed stream. Nothing is
+	/// compressed after this is called.
+	fn finish(&mut self, out: &mut Vec<u8>)
+		-> std::io::Result<()>;
+}
+
+/// When [`ReadWith::compressed`](struct.ReadWith.html#method.compressed)
+/// flushes its compressor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flush
+{
+	/// Only at the end, for the best compression.
+	End,
+	/// After every chunk, so that a streaming consumer can
+	/// decompress each chunk as soon as it is read.
+	EveryChunk,
+}
+
+/// A `Generator` that compresses the chunks of another
+///
+/// Created by [`ReadWith::compressed`](struct.ReadWith.html#method.compressed).
+pub struct Compressed<F, S, C>
+{
+	f: F,
+	compressor: C,
+	flush: Flush,
+	finished: bool,
+	chunk: PhantomData<fn() -> S>,
+}
+
+impl<F, S, C> Generator<Vec<u8>> for Compressed<F, S, C>
+	where F: Generator<S>,
+	S: AsRef<[u8]>,
+	C: Compressor
+{
+	fn generate(&mut self) -> std::io::Result<Option<Vec<u8>>>
+	{
+		while !self.finished
+		{
+			let mut out = vec!();
+			match self.f.generate()?
+			{
+				Some(chunk) =>
+				{
+					self.compressor.compress(chunk.as_ref(), &mut out)?;
+					if self.flush == Flush::EveryChunk
+						{ self.compressor.flush(&mut out)?; }
+				},
+				None =>
+				{
+					self.finished = true;
+					self.compressor.finish(&mut out)?;
+				},
+			}
+			if !out.is_empty()
+				{ return Ok(Some(out)); }
+		}
+		Ok(None)
+	}
+}
+
+impl<F, S, C> ReadWith<Compressed<F, S, C>, Vec<u8>>
+	where F: Generator<S>,
+	S: AsRef<[u8]>,
+	C: Compressor
+{
+	/// Create an object that will read the output of `compressor`
+	/// as it compresses each chunk from the given function.
+	pub fn compressed(f: F, compressor: C, flush: Flush) -> Self
+	{
+		ReadWith::with_generator(
+			Compressed
+			{
+				f,
+				compressor,
+				flush,
+				finished: false,
+				chunk: PhantomData,
+			}
+		)
+	}
+}
+
+/// Moves what an encoder has written to its `Vec` to `out`.
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+fn take(written: &mut Vec<u8>, out: &mut Vec<u8>)
+{
+	if out.is_empty()
+		{ std::mem::swap(written, out); }
+	else
+		{ out.append(written); }
+}
+
+/// Gzip as a `Compressor`, with the `gzip` feature
+#[cfg(feature = "gzip")]
+pub struct Gzip(flate2::write::GzEncoder<Vec<u8>>);
+
+#[cfg(feature = "gzip")]
+impl Gzip
+{
+	/// Compress at `level`, from 0 (none) to 9 (the best).
+	pub fn new(level: u32) -> Self
+	{
+		Gzip(flate2::write::GzEncoder::new(vec!(), flate2::Compression::new(level)))
+	}
+}
+
+#[cfg(feature = "gzip")]
+impl Default for Gzip
+{
+	/// Compress at the default level, 6.
+	fn default() -> Self
+	{
+		Gzip::new(6)
+	}
+}
+
+#[cfg(feature = "gzip")]
+impl Compressor for Gzip
+{
+	fn compress(&mut self, input: &[u8], out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		self.0.write_all(input)?;
+		take(self.0.get_mut(), out);
+		Ok(())
+	}
+
+	fn flush(&mut self, out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		self.0.flush()?;
+		take(self.0.get_mut(), out);
+		Ok(())
+	}
+
+	fn finish(&mut self, out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		self.0.try_finish()?;
+		take(self.0.get_mut(), out);
+		Ok(())
+	}
+}
+
+#[cfg(feature = "gzip")]
+impl<F, S> ReadWith<Compressed<F, S, Gzip>, Vec<u8>>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	/// Create an object that will read the chunks of the given
+	/// function compressed as gzip, at the default level.
+	///
+	/// ```rust
+	/// extern crate flate2;
+	/// extern crate read_with;
+	/// use std::io::Read;
+	/// use read_with::{ReadWith, Flush};
+	/// let mut chunks = vec!["one", "two"].into_iter();
+	/// let r = ReadWith::gzip(move || chunks.next(), Flush::End);
+	/// let mut s = String::new();
+	/// flate2::read::GzDecoder::new(r).read_to_string(&mut s).unwrap();
+	/// assert_eq!(s, "onetwo");
+	/// ```
+	pub fn gzip(f: F, flush: Flush) -> Self
+	{
+		ReadWith::compressed(f, Gzip::default(), flush)
+	}
+}
+
+/// Zstandard as a `Compressor`, with the `zstd` feature
+#[cfg(feature = "zstd")]
+pub struct Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>);
+
+#[cfg(feature = "zstd")]
+impl Zstd
+{
+	/// Compress at `level`, from 1 to 22, or 0 for the default.
+	pub fn new(level: i32) -> std::io::Result<Self>
+	{
+		zstd::stream::write::Encoder::new(vec!(), level).map(Zstd)
+	}
+}
+
+#[cfg(feature = "zstd")]
+impl Compressor for Zstd
+{
+	fn compress(&mut self, input: &[u8], out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		self.0.write_all(input)?;
+		take(self.0.get_mut(), out);
+		Ok(())
+	}
+
+	fn flush(&mut self, out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		self.0.flush()?;
+		take(self.0.get_mut(), out);
+		Ok(())
+	}
+
+	fn finish(&mut self, out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		self.0.do_finish()?;
+		take(self.0.get_mut(), out);
+		Ok(())
+	}
+}
+
+#[cfg(feature = "zstd")]
+impl<F, S> ReadWith<Compressed<F, S, Zstd>, Vec<u8>>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	/// Create an object that will read the chunks of the given
+	/// function compressed as Zstandard, at the default level.
+	///
+	/// Fails only if the compressor can't be created.
+	pub fn zstd(f: F, flush: Flush) -> std::io::Result<Self>
+	{
+		Ok(ReadWith::compressed(f, Zstd::new(0)?, flush))
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::{ReadWith, Compressor, Flush};
+	use std::io::Read;
+
+	/// Run-length encoding as (count, byte) pairs, holding back
+	/// the last run in case the next chunk continues it.
+	#[derive(Default)]
+	struct Rle
+	{
+		run: Option<(u8, u8)>,
+		finished: bool,
+	}
+
+	impl Compressor for Rle
+	{
+		fn compress(&mut self, input: &[u8], out: &mut Vec<u8>)
+			-> ::std::io::Result<()>
+		{
+			assert!(!self.finished);
+			for &b in input
+			{
+				match self.run
+				{
+					Some((n, r)) if r == b && n < 255 => self.run = Some((n+1, b)),
+					_ =>
+					{
+						self.flush(out)?;
+						self.run = Some((1, b));
+					},
+				}
+			}
+			Ok(())
+		}
+
+		fn flush(&mut self, out: &mut Vec<u8>)
+			-> ::std::io::Result<()>
+		{
+			if let Some((n, b)) = self.run.take()
+				{ out.extend_from_slice(&[n, b]); }
+			Ok(())
+		}
+
+		fn finish(&mut self, out: &mut Vec<u8>)
+			-> ::std::io::Result<()>
+		{
+			self.finished = true;
+			self.flush(out)
+		}
+	}
+
+	fn decompress(data: &[u8]) -> Vec<u8>
+	{
+		data.chunks(2)
+			.flat_map(|p| ::std::iter::repeat_n(p[1], p[0] as usize))
+			.collect()
+	}
+
+	#[test]
+	fn round_trip()
+	{
+		let chunks = ["aaab", "bbbbcc", "", "cd"];
+		let mut many = chunks.iter();
+		let mut compressed = vec!();
+		ReadWith::compressed(|| many.next(), Rle::default(), Flush::End)
+			.read_to_end(&mut compressed)
+			.unwrap();
+		assert_eq!(compressed, b"\x03a\x05b\x03c\x01d");
+		assert_eq!(decompress(&compressed), b"aaabbbbbcccd");
+	}
+
+	#[test]
+	fn flush_every_chunk()
+	{
+		let chunks = ["aaab", "bbbbcc", "", "cd"];
+		let mut many = chunks.iter();
+		let mut r = ReadWith::compressed(|| many.next(), Rle::default(), Flush::EveryChunk);
+		let mut so_far = vec!();
+		for expected in &["aaab", "aaabbbbbcc", "aaabbbbbcccd"]
+		{
+			so_far.extend_from_slice(&r.next_chunk().unwrap().unwrap());
+			assert_eq!(&decompress(&so_far), expected.as_bytes());
+		}
+		assert_eq!(r.next_chunk().unwrap(), None);
+	}
+
+	#[cfg(feature = "gzip")]
+	#[test]
+	fn gzip()
+	{
+		use flate2::read::GzDecoder;
+		use flate2::write::GzDecoder as Streaming;
+		use std::io::Write;
+		let chunks = ["one ", "two ", "", "three ", "one two three"];
+
+		let mut many = chunks.iter();
+		let mut s = String::new();
+		GzDecoder::new(ReadWith::gzip(|| many.next(), Flush::End))
+			.read_to_string(&mut s)
+			.unwrap();
+		assert_eq!(s, chunks.concat());
+
+		// each chunk can be decompressed as soon as it is read
+		let mut many = chunks.iter().filter(|c| !c.is_empty());
+		let mut r = ReadWith::gzip(|| many.next(), Flush::EveryChunk);
+		let mut d = Streaming::new(vec!());
+		let mut expected = String::new();
+		for c in chunks.iter().filter(|c| !c.is_empty())
+		{
+			expected.push_str(c);
+			d.write_all(&r.next_chunk().unwrap().unwrap()).unwrap();
+			d.flush().unwrap();
+			assert_eq!(d.get_ref(), expected.as_bytes());
+		}
+		d.write_all(&r.next_chunk().unwrap().unwrap()).unwrap();
+		assert_eq!(d.finish().unwrap(), expected.as_bytes());
+		assert_eq!(r.next_chunk().unwrap(), None);
+	}
+
+	#[cfg(feature = "zstd")]
+	#[test]
+	fn zstd()
+	{
+		use zstd::stream::write::Decoder as Streaming;
+		use std::io::Write;
+		let chunks = ["one ", "two ", "", "three ", "one two three"];
+
+		let mut many = chunks.iter();
+		let mut compressed = vec!();
+		ReadWith::zstd(|| many.next(), Flush::End).unwrap()
+			.read_to_end(&mut compressed)
+			.unwrap();
+		assert_eq!(::zstd::decode_all(&compressed[..]).unwrap(), chunks.concat().as_bytes());
+
+		let mut many = chunks.iter().filter(|c| !c.is_empty());
+		let mut r = ReadWith::zstd(|| many.next(), Flush::EveryChunk).unwrap();
+		let mut d = Streaming::new(vec!()).unwrap();
+		let mut all = vec!();
+		let mut expected = String::new();
+		for c in chunks.iter().filter(|c| !c.is_empty())
+		{
+			expected.push_str(c);
+			let next = r.next_chunk().unwrap().unwrap();
+			d.write_all(&next).unwrap();
+			d.flush().unwrap();
+			assert_eq!(d.get_ref(), expected.as_bytes());
+			all.extend_from_slice(&next);
+		}
+		all.extend_from_slice(&r.next_chunk().unwrap().unwrap());
+		assert_eq!(r.next_chunk().unwrap(), None);
+		assert_eq!(::zstd::decode_all(&all[..]).unwrap(), expected.as_bytes());
+	}
+}
@@ -0,0 +1,296 @@
+use std::io::{Read, BufRead};
+use ::{Generator, ReadWith};
+
+/// An incremental hash function, for
+/// [`ReadWith::digest`](struct.ReadWith.html#method.digest)
+///
+/// The `crc32` and `sha256` features, which are off by default,
+/// implement this for crc32fast's `Hasher` and sha2's `Sha256`, which
+/// are re-exported as `Crc32` and `Sha256`. A hasher from another
+/// crate needs a wrapper type to implement this:
+///
+/// ```rust
+/// use std::hash::Hasher;
+/// use std::io::Read;
+/// use read_with::{ReadWith, Digest};
+///
+/// struct Sip(std::collections::hash_map::DefaultHasher);
+///
+/// impl Digest for Sip
+/// {
+///     type Output = [u8; 8];
+///     fn update(&mut self, data: &[u8]) { self.0.write(data); }
+///     fn finish(&self) -> [u8; 8] { self.0.finish().to_be_bytes() }
+/// }
+///
+/// let mut r = ReadWith::from_iter(vec!["one", "two"]).digest(Sip(Default::default()));
+/// std::io::copy(&mut r, &mut std::io::sink()).unwrap();
+/// let mut whole = Sip(Default::default());
+/// whole.update(b"onetwo");
+/// assert_eq!(r.finish(), whole.finish());
+/// ```
+pub trait Digest
+{
+	/// The finished digest, such as `[u8; 32]`.
+	type Output: AsRef<[u8]>;
+
+	/// Hash `data` after everything given so far.
+	fn update(&mut self, data: &[u8]);
+
+	/// The digest of everything given so far.
+	fn finish(&self) -> Self::Output;
+}
+
+/// A reader that hashes the data of a `ReadWith` as it is read
+///
+/// Created by [`ReadWith::digest`](struct.ReadWith.html#method.digest).
+/// Only the bytes that are read are hashed, so the digest is
+/// that of the data delivered so far, whatever has been generated.
+pub struct Digested<F, S, D>
+	where F: Generator<S>,
+	S: AsRef<[u8]>,
+	D: Digest
+{
+	inner: ReadWith<F, S>,
+	hasher: D,
+	trailer: bool,
+	tail: Option<D::Output>,
+	tail_offset: usize,
+}
+
+impl<F, S> ReadWith<F, S>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	/// Hash the data with `hasher` as it is read.
+	pub fn digest<D>(self, hasher: D) -> Digested<F, S, D>
+		where D: Digest
+	{
+		Digested
+		{
+			inner: self,
+			hasher,
+			trailer: false,
+			tail: None,
+			tail_offset: 0,
+		}
+	}
+}
+
+impl<F, S, D> Digested<F, S, D>
+	where F: Generator<S>,
+	S: AsRef<[u8]>,
+	D: Digest
+{
+	/// Read the digest after the end of the data.
+	///
+	/// The digest itself is not hashed.
+	pub fn trailer(mut self) -> Self
+	{
+		self.trailer = true;
+		self
+	}
+
+	/// The digest of the data read so far, not including the trailer.
+	pub fn finish(&self) -> D::Output
+	{
+		self.hasher.finish()
+	}
+
+	/// Stop hashing, returning the reader.
+	pub fn into_inner(self) -> ReadWith<F, S>
+	{
+		self.inner
+	}
+}
+
+impl<F, S, D> Read for Digested<F, S, D>
+	where F: Generator<S>,
+	S: AsRef<[u8]>,
+	D: Digest
+{
+	fn read(&mut self, buf: &mut [u8])
+		-> std::io::Result<usize>
+	{
+		if self.tail.is_none()
+		{
+			let n = self.inner.read(buf)?;
+			self.hasher.update(&buf[..n]);
+			if n > 0 || buf.is_empty() || !self.trailer
+				{ return Ok(n); }
+			self.tail = Some(self.hasher.finish());
+		}
+		let tail = match self.tail
+		{
+			Some(ref t) => &t.as_ref()[self.tail_offset..],
+			None => &[],
+		};
+		let n = tail.len().min(buf.len());
+		buf[..n].copy_from_slice(&tail[..n]);
+		self.tail_offset += n;
+		Ok(n)
+	}
+}
+
+impl<F, S, D> BufRead for Digested<F, S, D>
+	where F: Generator<S>,
+	S: AsRef<[u8]>,
+	D: Digest
+{
+	fn fill_buf(&mut self)
+		-> std::io::Result<&[u8]>
+	{
+		if self.tail.is_none()
+		{
+			if !self.trailer || !self.inner.fill_buf()?.is_empty()
+				{ return self.inner.fill_buf(); }
+			self.tail = Some(self.hasher.finish());
+		}
+		match self.tail
+		{
+			Some(ref t) => Ok(&t.as_ref()[self.tail_offset..]),
+			None => Ok(&[]),
+		}
+	}
+
+	fn consume(&mut self, amt: usize)
+	{
+		match self.tail
+		{
+			Some(ref t) =>
+				self.tail_offset = (self.tail_offset + amt).min(t.as_ref().len()),
+			None =>
+			{
+				let buffered = self.inner.buffered();
+				self.hasher.update(&buffered[..amt.min(buffered.len())]);
+				self.inner.consume(amt);
+			},
+		}
+	}
+}
+
+/// The CRC-32 of zlib, gzip and PNG, big-endian as PNG writes it
+#[cfg(feature = "crc32")]
+impl Digest for crc32fast::Hasher
+{
+	type Output = [u8; 4];
+
+	fn update(&mut self, data: &[u8])
+	{
+		crc32fast::Hasher::update(self, data);
+	}
+
+	fn finish(&self) -> [u8; 4]
+	{
+		self.clone().finalize().to_be_bytes()
+	}
+}
+
+#[cfg(feature = "sha256")]
+impl Digest for sha2::Sha256
+{
+	type Output = [u8; 32];
+
+	fn update(&mut self, data: &[u8])
+	{
+		sha2::Digest::update(self, data);
+	}
+
+	fn finish(&self) -> [u8; 32]
+	{
+		sha2::Digest::finalize(self.clone()).into()
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::{ReadWith, Digest};
+	use std::io::{Read, BufRead};
+
+	#[cfg(any(feature = "crc32", feature = "sha256"))]
+	fn hex(d: &[u8]) -> String
+	{
+		d.iter().map(|b| format!("{:02x}", b)).collect()
+	}
+
+	/// Keeps the bytes it is given.
+	#[derive(Default)]
+	struct Everything(Vec<u8>);
+
+	impl Digest for Everything
+	{
+		type Output = Vec<u8>;
+
+		fn update(&mut self, data: &[u8])
+		{
+			self.0.extend_from_slice(data);
+		}
+
+		fn finish(&self) -> Vec<u8>
+		{
+			self.0.clone()
+		}
+	}
+
+	#[test]
+	fn delivered()
+	{
+		let mut r = ReadWith::from_iter(vec!["abc", "defg", "hi"])
+			.digest(Everything::default())
+			.trailer();
+		let mut buf = [0u8; 2];
+		r.read_exact(&mut buf).unwrap();
+		// "c" has been generated but not read
+		assert_eq!(r.finish(), b"ab");
+		assert_eq!(r.fill_buf().unwrap(), b"c");
+		r.consume(1);
+		let mut buf = [0u8; 5];
+		assert_eq!(r.read(&mut buf).unwrap(), 5);
+		assert_eq!(r.finish(), b"abcdefgh");
+		let mut rest = vec!();
+		r.read_to_end(&mut rest).unwrap();
+		assert_eq!(rest, b"iabcdefghi");
+		assert_eq!(r.finish(), b"abcdefghi");
+	}
+
+	#[cfg(feature = "crc32")]
+	#[test]
+	fn crc32()
+	{
+		let mut c = ::Crc32::new();
+		let mut r = ReadWith::from_iter(vec!["1234", "56789"]).digest(c.clone());
+		r.read_to_end(&mut vec!()).unwrap();
+		assert_eq!(r.finish(), [0xcb, 0xf4, 0x39, 0x26]);
+		assert_eq!(c.finish(), [0; 4]);
+		c.update(b"The quick brown fox ");
+		c.update(b"jumps over the lazy dog");
+		assert_eq!(hex(&c.finish()), "414fa339");
+	}
+
+	#[cfg(feature = "sha256")]
+	#[test]
+	fn sha256()
+	{
+		let data: Vec<u8> = (0..200u8).collect();
+		let mut r = ReadWith::from_iter(data.chunks(7)).digest(::Sha256::default()).trailer();
+		let mut out = vec!();
+		r.read_to_end(&mut out).unwrap();
+		assert_eq!(&out[..200], &data[..]);
+		assert_eq!(&out[200..], r.finish());
+
+		let mut one = ::Sha256::default();
+		one.update(&data);
+		assert_eq!(one.finish(), r.finish());
+		assert_eq!(hex(&::Sha256::default().finish()),
+			"e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+		one = ::Sha256::default();
+		one.update(b"abc");
+		assert_eq!(hex(&one.finish()),
+			"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+		one = ::Sha256::default();
+		one.update(&[b'a'; 56]);
+		assert_eq!(hex(&one.finish()),
+			"b35439a4ac6f0948b6d6f9e3c6af0f5f590ce20f1bde7090ef7970686ec6738a");
+	}
+}
@@ -0,0 +1,413 @@
+use ::Transform;
+
+const BASE64: &[u8; 64] =
+	b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+/// How the output of an encoder is divided into lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineWrap
+{
+	/// All on one line.
+	None,
+	/// Lines of 76 characters ending with `\r\n`, as in MIME.
+	Mime,
+	/// Lines of 64 characters ending with `\n`, as in PEM.
+	Pem,
+	/// Lines of this many characters ending with `\n`.
+	Columns(usize),
+}
+
+impl LineWrap
+{
+	fn line(&self) -> Option<(usize, &'static [u8])>
+	{
+		match *self
+		{
+			LineWrap::None => None,
+			LineWrap::Mime => Some((76, b"\r\n")),
+			LineWrap::Pem => Some((64, b"\n")),
+			LineWrap::Columns(n) => Some((n, b"\n")),
+		}
+	}
+}
+
+/// Writes encoded characters, breaking the lines as needed
+struct Wrapper
+{
+	wrap: LineWrap,
+	column: usize,
+}
+
+impl Wrapper
+{
+	fn new(wrap: LineWrap) -> Self
+	{
+		if let LineWrap::Columns(n) = wrap
+			{ assert!(n > 0, "line length must not be zero"); }
+		Wrapper { wrap, column: 0 }
+	}
+
+	fn push(&mut self, out: &mut Vec<u8>, c: u8)
+	{
+		if let Some((width, ending)) = self.wrap.line()
+		{
+			if self.column == width
+			{
+				out.extend_from_slice(ending);
+				self.column = 0;
+			}
+		}
+		out.push(c);
+		self.column += 1;
+	}
+
+	/// End the last line.
+	fn finish(&mut self, out: &mut Vec<u8>)
+	{
+		if let Some((_, ending)) = self.wrap.line()
+		{
+			if self.column > 0
+				{ out.extend_from_slice(ending); }
+		}
+		self.column = 0;
+	}
+}
+
+fn invalid(msg: &'static str) -> std::io::Error
+{
+	std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+fn is_space(b: u8) -> bool
+{
+	b == b' ' || b == b'\t' || b == b'\r' || b == b'\n'
+}
+
+/// A `Transform` that encodes as base64, with padding
+///
+/// The bytes that don't make up a multiple of 3 are held until
+/// the next chunk, so the output is the same however the data
+/// is divided.
+///
+/// ```rust
+/// use std::io::Read;
+/// use read_with::{ReadWith, Base64Encoder, LineWrap};
+/// let mut s = String::new();
+/// ReadWith::from_iter(vec!["he", "llo", " w", "orld"])
+///     .transform(Base64Encoder::new(LineWrap::None))
+///     .read_to_string(&mut s)
+///     .unwrap();
+/// assert_eq!(s, "aGVsbG8gd29ybGQ=");
+/// ```
+pub struct Base64Encoder
+{
+	held: [u8; 3],
+	held_len: usize,
+	wrapper: Wrapper,
+}
+
+impl Base64Encoder
+{
+	/// Create an encoder that divides its output into lines with `wrap`.
+	///
+	/// Panics if given `LineWrap::Columns(0)`.
+	pub fn new(wrap: LineWrap) -> Self
+	{
+		Base64Encoder
+		{
+			held: [0; 3],
+			held_len: 0,
+			wrapper: Wrapper::new(wrap),
+		}
+	}
+
+	fn encode(&mut self, group: [u8; 3], len: usize, out: &mut Vec<u8>)
+	{
+		let n = (group[0] as usize) << 16 | (group[1] as usize) << 8 | group[2] as usize;
+		for i in 0..4
+		{
+			let c = if i <= len
+				{ BASE64[(n >> (18 - 6*i)) & 0x3f] }
+			else
+				{ b'=' };
+			self.wrapper.push(out, c);
+		}
+	}
+}
+
+impl Transform for Base64Encoder
+{
+	fn transform(&mut self, input: &[u8], out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		for &b in input
+		{
+			self.held[self.held_len] = b;
+			self.held_len += 1;
+			if self.held_len == 3
+			{
+				let group = self.held;
+				self.encode(group, 3, out);
+				self.held_len = 0;
+			}
+		}
+		Ok(())
+	}
+
+	fn finish(&mut self, out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		if self.held_len > 0
+		{
+			let mut group = [0; 3];
+			group[..self.held_len].copy_from_slice(&self.held[..self.held_len]);
+			let len = self.held_len;
+			self.encode(group, len, out);
+			self.held_len = 0;
+		}
+		self.wrapper.finish(out);
+		Ok(())
+	}
+}
+
+/// A `Transform` that decodes base64
+///
+/// Whitespace and line breaks are ignored, and the padding
+/// at the end may be left out; if it is there, it must be
+/// complete, and unused bits must be zero.
+pub struct Base64Decoder
+{
+	held: [u8; 4],
+	held_len: usize,
+	/// The number of `=` seen after the last group
+	pads: usize,
+}
+
+impl Base64Decoder
+{
+	/// Create a decoder.
+	pub fn new() -> Self
+	{
+		Base64Decoder
+		{
+			held: [0; 4],
+			held_len: 0,
+			pads: 0,
+		}
+	}
+
+	fn decode(&self, out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		let n = self.held[..self.held_len].iter()
+			.enumerate()
+			.fold(0u32, |n, (i, &v)| n | (v as u32) << (18 - 6*i));
+		let len = self.held_len-1;
+		// the bits after the last byte must be zero
+		if n & (0xff_ffff >> (8*len)) != 0
+			{ return Err(invalid("non-canonical base64 data")); }
+		let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+		out.extend_from_slice(&bytes[..len]);
+		Ok(())
+	}
+}
+
+impl Default for Base64Decoder
+{
+	fn default() -> Self
+	{
+		Base64Decoder::new()
+	}
+}
+
+impl Transform for Base64Decoder
+{
+	fn transform(&mut self, input: &[u8], out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		for &b in input
+		{
+			if is_space(b)
+				{ continue; }
+			if b == b'='
+			{
+				if self.held_len < 2 || self.held_len + self.pads == 4
+					{ return Err(invalid("misplaced base64 padding")); }
+				self.pads += 1;
+				continue;
+			}
+			if self.pads > 0
+				{ return Err(invalid("base64 data after its padding")); }
+			let v = BASE64.iter().position(|&c| c == b)
+				.ok_or_else(|| invalid("invalid base64 character"))?;
+			self.held[self.held_len] = v as u8;
+			self.held_len += 1;
+			if self.held_len == 4
+			{
+				self.decode(out)?;
+				self.held_len = 0;
+			}
+		}
+		Ok(())
+	}
+
+	fn finish(&mut self, out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		match self.held_len
+		{
+			0 => Ok(()),
+			1 => Err(invalid("truncated base64 data")),
+			n if self.pads > 0 && n + self.pads != 4 =>
+				Err(invalid("wrong amount of base64 padding")),
+			_ =>
+			{
+				self.decode(out)?;
+				self.held_len = 0;
+				self.pads = 0;
+				Ok(())
+			},
+		}
+	}
+}
+
+/// A `Transform` that encodes as lowercase hexadecimal
+pub struct HexEncoder
+{
+	wrapper: Wrapper,
+}
+
+impl HexEncoder
+{
+	/// Create an encoder that divides its output into lines with `wrap`.
+	///
+	/// Panics if given `LineWrap::Columns(0)`.
+	pub fn new(wrap: LineWrap) -> Self
+	{
+		HexEncoder { wrapper: Wrapper::new(wrap) }
+	}
+}
+
+impl Transform for HexEncoder
+{
+	fn transform(&mut self, input: &[u8], out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		for &b in input
+		{
+			self.wrapper.push(out, HEX[(b >> 4) as usize]);
+			self.wrapper.push(out, HEX[(b & 0xf) as usize]);
+		}
+		Ok(())
+	}
+
+	fn finish(&mut self, out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		self.wrapper.finish(out);
+		Ok(())
+	}
+}
+
+/// A `Transform` that decodes hexadecimal in either case
+///
+/// Whitespace and line breaks are ignored.
+#[derive(Default)]
+pub struct HexDecoder
+{
+	high: Option<u8>,
+}
+
+impl HexDecoder
+{
+	/// Create a decoder.
+	pub fn new() -> Self
+	{
+		HexDecoder { high: None }
+	}
+}
+
+impl Transform for HexDecoder
+{
+	fn transform(&mut self, input: &[u8], out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		for &b in input
+		{
+			if is_space(b)
+				{ continue; }
+			let v = (b as char).to_digit(16)
+				.ok_or_else(|| invalid("invalid hexadecimal digit"))? as u8;
+			match self.high.take()
+			{
+				Some(h) => out.push(h << 4 | v),
+				None => self.high = Some(v),
+			}
+		}
+		Ok(())
+	}
+
+	fn finish(&mut self, _out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		if self.high.is_some()
+			{ return Err(invalid("odd number of hexadecimal digits")); }
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::{ReadWith, Transform, Base64Encoder, Base64Decoder, HexEncoder, HexDecoder, LineWrap};
+	use std::io::{Read, ErrorKind};
+
+	fn apply<T: Transform>(chunks: Vec<&[u8]>, t: T) -> ::std::io::Result<Vec<u8>>
+	{
+		let mut out = vec!();
+		ReadWith::from_iter(chunks).transform(t).read_to_end(&mut out)?;
+		Ok(out)
+	}
+
+	#[test]
+	fn base64()
+	{
+		let data: Vec<u8> = (0..=255).collect();
+		for split in 1..8
+		{
+			let chunks: Vec<&[u8]> = data.chunks(split).collect();
+			let encoded = apply(chunks, Base64Encoder::new(LineWrap::Pem)).unwrap();
+			let lines: Vec<&[u8]> = encoded.split(|&b| b == b'\n').collect();
+			assert_eq!(lines.len(), 7);
+			assert_eq!(lines[0].len(), 64);
+			assert_eq!(lines[5], b"8PHy8/T19vf4+fr7/P3+/w==");
+			assert_eq!(lines[6], b"");
+
+			let chunks: Vec<&[u8]> = encoded.chunks(split).collect();
+			assert_eq!(apply(chunks, Base64Decoder::new()).unwrap(), data);
+		}
+
+		for &(plain, encoded) in &[(&b""[..], &b""[..]), (b"f", b"Zg=="), (b"fo", b"Zm8="), (b"foo", b"Zm9v")]
+		{
+			assert_eq!(apply(vec![plain], Base64Encoder::new(LineWrap::Mime)).unwrap(),
+				if encoded.is_empty() { vec!() } else { [encoded, b"\r\n"].concat() });
+			assert_eq!(apply(vec![encoded], Base64Decoder::new()).unwrap(), plain);
+		}
+		assert_eq!(apply(vec![b"Zm8"], Base64Decoder::new()).unwrap(), b"fo");
+		assert_eq!(apply(vec![b"Zm=9"], Base64Decoder::new()).unwrap_err().kind(), ErrorKind::InvalidData);
+		for &bad in &[&b"Zg====="[..], b"Zg=", b"Zg=\n==", b"Zm8==", b"Zh==", b"Zm9="]
+			{ assert_eq!(apply(vec![bad], Base64Decoder::new()).unwrap_err().kind(), ErrorKind::InvalidData); }
+		assert_eq!(apply(vec![b"Z"], Base64Decoder::new()).unwrap_err().kind(), ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn hex()
+	{
+		let encoded = apply(vec![b"\x00\xff", b"\x10"], HexEncoder::new(LineWrap::Columns(4))).unwrap();
+		assert_eq!(encoded, b"00ff\n10\n");
+		assert_eq!(apply(vec![b"00F", b"f\n1", b"0"], HexDecoder::new()).unwrap(), b"\x00\xff\x10");
+		assert_eq!(apply(vec![b"0"], HexDecoder::new()).unwrap_err().kind(), ErrorKind::InvalidData);
+		assert_eq!(apply(vec![b"0g"], HexDecoder::new()).unwrap_err().kind(), ErrorKind::InvalidData);
+	}
+}
@@ -0,0 +1,281 @@
+use ::{Generator, ReadWith};
+
+/// The encoding of a length written before each chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthPrefix
+{
+	/// Two bytes, big-endian
+	U16Be,
+	/// Two bytes, little-endian
+	U16Le,
+	/// Four bytes, big-endian
+	U32Be,
+	/// Four bytes, little-endian
+	U32Le,
+	/// An unsigned LEB128 varint, as used by Protocol Buffers
+	Varint,
+}
+
+/// How [`ReadWith::framed`](struct.ReadWith.html#method.framed)
+/// marks the chunks of its function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing<'a>
+{
+	/// These bytes go between each chunk.
+	Delimiter(&'a [u8]),
+	/// These bytes go after each chunk.
+	Terminator(&'a [u8]),
+	/// The length of each chunk goes before it.
+	LengthPrefix(LengthPrefix),
+	/// Each chunk becomes a netstring, `<length>:<chunk>,`.
+	Netstring,
+}
+
+/// A chunk or the bytes that frame it
+pub enum FramePart<'a, S>
+{
+	/// A chunk from the function
+	Chunk(S),
+	/// A delimiter or terminator
+	Slice(&'a [u8]),
+	/// An encoded length, stored inline
+	Header([u8; 24], usize),
+}
+
+impl<'a, S> AsRef<[u8]> for FramePart<'a, S>
+	where S: AsRef<[u8]>
+{
+	fn as_ref(&self) -> &[u8]
+	{
+		match *self
+		{
+			FramePart::Chunk(ref c) => c.as_ref(),
+			FramePart::Slice(s) => s,
+			FramePart::Header(ref h, len) => &h[..len],
+		}
+	}
+}
+
+/// A `Generator` that adds framing around the chunks of another
+///
+/// Created by [`ReadWith::framed`](struct.ReadWith.html#method.framed).
+pub struct Framed<'a, F, S>
+{
+	f: F,
+	framing: Framing<'a>,
+	started: bool,
+	chunk: Option<S>,
+	trailer: Option<&'a [u8]>,
+}
+
+fn too_long() -> std::io::Error
+{
+	std::io::Error::new(
+		std::io::ErrorKind::InvalidData,
+		"chunk is too long for its length prefix",
+	)
+}
+
+fn length_prefix(prefix: LengthPrefix, len: usize)
+	-> std::io::Result<([u8; 24], usize)>
+{
+	let mut h = [0u8; 24];
+	let n = match prefix
+	{
+		LengthPrefix::U16Be | LengthPrefix::U16Le =>
+		{
+			if len > u16::MAX as usize
+				{ return Err(too_long()); }
+			let bytes = if prefix == LengthPrefix::U16Be
+				{ (len as u16).to_be_bytes() }
+			else
+				{ (len as u16).to_le_bytes() };
+			h[..2].copy_from_slice(&bytes);
+			2
+		},
+		LengthPrefix::U32Be | LengthPrefix::U32Le =>
+		{
+			if len > u32::MAX as usize
+				{ return Err(too_long()); }
+			let bytes = if prefix == LengthPrefix::U32Be
+				{ (len as u32).to_be_bytes() }
+			else
+				{ (len as u32).to_le_bytes() };
+			h[..4].copy_from_slice(&bytes);
+			4
+		},
+		LengthPrefix::Varint =>
+		{
+			let mut len = len as u64;
+			let mut n = 0;
+			loop
+			{
+				let b = (len & 0x7f) as u8;
+				len >>= 7;
+				if len == 0
+				{
+					h[n] = b;
+					break n+1;
+				}
+				h[n] = b | 0x80;
+				n += 1;
+			}
+		},
+	};
+	Ok((h, n))
+}
+
+fn netstring_header(len: usize) -> ([u8; 24], usize)
+{
+	let mut digits = [0u8; 20];
+	let mut start = digits.len();
+	let mut len = len;
+	loop
+	{
+		start -= 1;
+		digits[start] = b'0' + (len % 10) as u8;
+		len /= 10;
+		if len == 0
+			{ break; }
+	}
+	let n = digits.len()-start;
+	let mut h = [0u8; 24];
+	h[..n].copy_from_slice(&digits[start..]);
+	h[n] = b':';
+	(h, n+1)
+}
+
+impl<'a, F, S> Generator<FramePart<'a, S>> for Framed<'a, F, S>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	fn generate(&mut self) -> std::io::Result<Option<FramePart<'a, S>>>
+	{
+		if let Some(c) = self.chunk.take()
+			{ return Ok(Some(FramePart::Chunk(c))); }
+		if let Some(t) = self.trailer.take()
+			{ return Ok(Some(FramePart::Slice(t))); }
+
+		let c = match self.f.generate()?
+		{
+			Some(c) => c,
+			None => return Ok(None),
+		};
+		let len = c.as_ref().len();
+		let before = match self.framing
+		{
+			Framing::Delimiter(d) =>
+			{
+				if !self.started
+				{
+					self.started = true;
+					return Ok(Some(FramePart::Chunk(c)));
+				}
+				FramePart::Slice(d)
+			},
+			Framing::Terminator(t) =>
+			{
+				self.trailer = Some(t);
+				return Ok(Some(FramePart::Chunk(c)));
+			},
+			Framing::LengthPrefix(p) =>
+			{
+				let (h, n) = length_prefix(p, len)?;
+				FramePart::Header(h, n)
+			},
+			Framing::Netstring =>
+			{
+				self.trailer = Some(b",");
+				let (h, n) = netstring_header(len);
+				FramePart::Header(h, n)
+			},
+		};
+		self.chunk = Some(c);
+		Ok(Some(before))
+	}
+}
+
+impl<'a, F, S> ReadWith<Framed<'a, F, S>, FramePart<'a, S>>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	/// Create an object that will read from the given function,
+	/// adding delimiters or length prefixes around each chunk.
+	///
+	/// The chunks themselves are not copied or reallocated.
+	///
+	/// ```rust
+	/// use std::io::Read;
+	/// use read_with::{ReadWith, Framing};
+	/// let mut many_strings = vec!["one", "two", "three"].into_iter();
+	/// let mut r = ReadWith::framed(|| many_strings.next(), Framing::Terminator(b"\n"));
+	/// let mut s = String::new();
+	/// r.read_to_string(&mut s).unwrap();
+	/// assert_eq!(s, "one\ntwo\nthree\n");
+	/// ```
+	pub fn framed(f: F, framing: Framing<'a>) -> Self
+	{
+		ReadWith::with_generator(
+			Framed
+			{
+				f,
+				framing,
+				started: false,
+				chunk: None,
+				trailer: None,
+			}
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::{ReadWith, Framing, LengthPrefix};
+	use std::io::{Read, ErrorKind};
+
+	fn framed(chunks: Vec<&[u8]>, framing: Framing) -> Vec<u8>
+	{
+		let mut chunks = chunks.into_iter();
+		let mut output = vec!();
+		ReadWith::framed(|| chunks.next(), framing)
+			.read_to_end(&mut output)
+			.unwrap();
+		output
+	}
+
+	#[test]
+	fn delimiters()
+	{
+		let chunks = vec![&b"one"[..], b"", b"two"];
+		assert_eq!(framed(chunks.clone(), Framing::Delimiter(b", ")), b"one, , two");
+		assert_eq!(framed(chunks.clone(), Framing::Terminator(b"\n")), b"one\n\ntwo\n");
+		assert_eq!(framed(chunks, Framing::Netstring), b"3:one,0:,3:two,");
+		let long = vec![b'x'; 1234];
+		assert_eq!(&framed(vec![&long[..]], Framing::Netstring)[..6], b"1234:x");
+		assert_eq!(framed(vec!(), Framing::Delimiter(b", ")), b"");
+	}
+
+	#[test]
+	fn length_prefixes()
+	{
+		let long = vec![b'x'; 300];
+		let chunks = vec![&b"one"[..], &long];
+		let with = |prefix| framed(chunks.clone(), Framing::LengthPrefix(prefix));
+
+		assert_eq!(&with(LengthPrefix::U16Be)[..5], b"\x00\x03one");
+		assert_eq!(&with(LengthPrefix::U16Be)[5..7], b"\x01\x2c");
+		assert_eq!(&with(LengthPrefix::U16Le)[5..7], b"\x2c\x01");
+		assert_eq!(&with(LengthPrefix::U32Be)[..7], b"\x00\x00\x00\x03one");
+		assert_eq!(&with(LengthPrefix::U32Le)[7..11], b"\x2c\x01\x00\x00");
+		assert_eq!(&with(LengthPrefix::Varint)[..6], b"\x03one\xac\x02");
+		assert_eq!(with(LengthPrefix::Varint).len(), 3+1+300+2);
+
+		let too_long = vec![0u8; 70000];
+		let mut chunks = vec![&too_long[..]].into_iter();
+		let e = ReadWith::framed(|| chunks.next(), Framing::LengthPrefix(LengthPrefix::U16Le))
+			.read_to_end(&mut vec!())
+			.unwrap_err();
+		assert_eq!(e.kind(), ErrorKind::InvalidData);
+	}
+}
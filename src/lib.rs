@@ -22,23 +22,242 @@
 //!     &mut std::io::stdout(),
 //! ).unwrap();
 //! ```
+//!
+//! An iterator can be read from directly with
+//! [`IntoRead`](trait.IntoRead.html):
+//!
+//! ```rust
+//! use read_with::IntoRead;
+//! let many_strings = ["one", "two", "three"];
+//! std::io::copy(
+//!     &mut many_strings.iter().into_read(),
+//!     &mut std::io::stdout(),
+//! ).unwrap();
+//! ```
+//!
+//! [`WriteWith`](struct.WriteWith.html) does the opposite, giving
+//! everything written to it to a function.
+//!
+//! [`AsyncReadWith`](struct.AsyncReadWith.html) reads asynchronously
+//! from a polling function, a function returning futures or,
+//! with the `futures-core` feature, a `Stream`.
+//!
+//! [`SeekWith`](struct.SeekWith.html) also implements `Seek`, by giving
+//! its function the offset at which to generate data.
+//!
+//! [`ChunksOf`](struct.ChunksOf.html) goes the other way, splitting
+//! any `Read` back into chunks.
+
+#[cfg(feature = "bytes")]
+extern crate bytes;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+#[cfg(feature = "serde")]
+extern crate csv;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[cfg(feature = "zstd")]
+extern crate zstd;
+#[cfg(feature = "crc32")]
+extern crate crc32fast;
+#[cfg(feature = "sha256")]
+extern crate sha2;
+#[cfg(feature = "futures-core")]
+extern crate futures_core;
+#[cfg(feature = "futures-io")]
+extern crate futures_io;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(test)]
+extern crate futures;
+
+use std::io::{Read, BufRead, IoSliceMut};
 
-use std::io::Read;
+mod write_with;
+pub use write_with::{WriteWith, Chunking};
+mod async_read_with;
+pub use async_read_with::{AsyncReadWith, AsyncGenerator, FutureFn};
+#[cfg(feature = "futures-core")]
+pub use async_read_with::{FromStream, FromTryStream};
+mod seek_with;
+pub use seek_with::{SeekWith, ChunkSource};
+mod framing;
+pub use framing::{Framing, LengthPrefix, Framed, FramePart};
+mod chunks_of;
+pub use chunks_of::{ChunksOf, Split};
+#[cfg(feature = "serde")]
+mod records;
+#[cfg(feature = "serde")]
+pub use records::{Records, RecordsReadWith, Record, RecordFormat};
+mod compress;
+pub use compress::{Compressor, Compressed, Flush};
+#[cfg(feature = "gzip")]
+pub use compress::Gzip;
+#[cfg(feature = "zstd")]
+pub use compress::Zstd;
+mod transform;
+pub use transform::{Transform, Transformed, MapChunks, FilterChunks, Inspect};
+mod encoding;
+pub use encoding::{Base64Encoder, Base64Decoder, HexEncoder, HexDecoder, LineWrap};
+mod chain;
+pub use chain::{Either, Chain, Interleave, Concat, RoundRobin, ReadChunks};
+mod prefetch;
+pub use prefetch::{Prefetch, PrefetchReadWith};
+mod parallel;
+pub use parallel::{Parallel, ParallelReadWith};
+mod throttle;
+pub use throttle::{Throttled, Clock, SystemClock, Rate};
+mod progress;
+pub use progress::{Progress, Counters, Observed};
+mod digest;
+pub use digest::{Digest, Digested};
+#[cfg(feature = "crc32")]
+pub use crc32fast::Hasher as Crc32;
+#[cfg(feature = "sha256")]
+pub use sha2::Sha256;
+#[cfg(feature = "bytes")]
+mod buf_with;
+#[cfg(feature = "bytes")]
+pub use buf_with::BufWith;
+
+/// A source of chunks for a [`ReadWith`](struct.ReadWith.html).
+///
+/// This is implemented for every `FnMut() -> Option<S>` and,
+/// through [`TryFn`](struct.TryFn.html), for functions that
+/// can fail.
+pub trait Generator<S>
+{
+	/// Produce the next chunk, `None` at the end of the data.
+	fn generate(&mut self) -> std::io::Result<Option<S>>;
+}
+
+impl<F, S> Generator<S> for F
+	where F: FnMut() -> Option<S>
+{
+	fn generate(&mut self) -> std::io::Result<Option<S>>
+	{
+		Ok(self())
+	}
+}
+
+/// Wraps a function that returns a `Result<Option<S>, E>`
+///
+/// Created by [`ReadWith::try_new`](struct.ReadWith.html#method.try_new),
+/// or directly to pass such a function where a `Generator` is expected.
+pub struct TryFn<F>(pub F);
+
+impl<F, S, E> Generator<S> for TryFn<F>
+	where F: FnMut() -> Result<Option<S>, E>,
+	E: Into<std::io::Error>
+{
+	fn generate(&mut self) -> std::io::Result<Option<S>>
+	{
+		(self.0)().map_err(Into::into)
+	}
+}
+
+/// Wraps an `Iterator` of chunks
+///
+/// Created by [`ReadWith::from_iter`](struct.ReadWith.html#method.from_iter).
+pub struct Iter<I>(I);
+
+impl<I, S> Generator<S> for Iter<I>
+	where I: Iterator<Item=S>
+{
+	fn generate(&mut self) -> std::io::Result<Option<S>>
+	{
+		Ok(self.0.next())
+	}
+}
+
+/// Chunks that can have bytes removed from their front
+///
+/// Needed by [`ReadWith::next_chunk`](struct.ReadWith.html#method.next_chunk)
+/// to hand out a chunk that was partially read. A `Vec<u8>` has the
+/// rest of its bytes moved to its front; with the `bytes` feature,
+/// `Bytes` and `BytesMut` are only sliced.
+pub trait Advance
+{
+	/// Remove the first `n` bytes.
+	fn advance(&mut self, n: usize);
+}
+
+impl Advance for Vec<u8>
+{
+	fn advance(&mut self, n: usize)
+	{
+		self.drain(..n);
+	}
+}
+
+impl Advance for &[u8]
+{
+	fn advance(&mut self, n: usize)
+	{
+		*self = &self[n..];
+	}
+}
+
+#[cfg(feature = "bytes")]
+impl Advance for bytes::Bytes
+{
+	fn advance(&mut self, n: usize)
+	{
+		bytes::Buf::advance(self, n);
+	}
+}
+
+#[cfg(feature = "bytes")]
+impl Advance for bytes::BytesMut
+{
+	fn advance(&mut self, n: usize)
+	{
+		bytes::Buf::advance(self, n);
+	}
+}
+
+/// How much a [`ReadWith`](struct.ReadWith.html) reads in each call to `read`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadPolicy
+{
+	/// Keep calling the function until the buffer is full or
+	/// the data ends. This is the default.
+	FillBuffer,
+	/// Don't call the function again once some bytes have been
+	/// copied, so that each chunk is delivered without waiting for the next.
+	OneChunk,
+	/// Like `FillBuffer`, but return the bytes read so far when
+	/// the function fails with `ErrorKind::WouldBlock`. The error
+	/// is only returned when no bytes could be read.
+	UntilWouldBlock,
+}
 
 /// An object that implements the `Read` trait
 pub struct ReadWith<F, S>
-	where F: FnMut() -> Option<S>,
-	S: AsRef<[u8]> + Default
+	where F: Generator<S>,
+	S: AsRef<[u8]>
 {
 	f: F,
-	current: S,
+	current: Option<S>,
 	offset: usize,
-	end: bool
+	end: bool,
+	error: Option<std::io::Error>,
+	policy: ReadPolicy,
+	len: Option<u64>,
+	generated: u64,
+	chunks: u64,
+	consumed: u64,
+	/// Bytes in `front` given to `unread` beyond those consumed
+	pushed: u64,
+	front: Vec<u8>,
+	front_offset: usize,
 }
 
 impl<F, S> ReadWith<F, S>
 	where F: FnMut() -> Option<S>,
-	S: AsRef<[u8]> + Default
+	S: AsRef<[u8]>
 {
 	/// Create an object that will read from the given function.
 	///
@@ -46,47 +265,599 @@ impl<F, S> ReadWith<F, S>
 	/// The function may return anything that can be turned into
 	/// a `&[u8]` which includes `String` and `&str`.
 	pub fn new(f: F) -> Self
+	{
+		ReadWith::with_generator(f)
+	}
+}
+
+impl<F, S, E> ReadWith<TryFn<F>, S>
+	where F: FnMut() -> Result<Option<S>, E>,
+	E: Into<std::io::Error>,
+	S: AsRef<[u8]>
+{
+	/// Create an object that will read from a function that can fail.
+	///
+	/// Keeps on reading from `f` until it returns `Ok(None)`.
+	/// An error from `f` is returned by `read`; if some bytes
+	/// were already copied during that call, they are returned
+	/// first and the error is reported by the next call.
+	///
+	/// ```rust
+	/// use std::io::Read;
+	/// let mut lines = vec!["one", "two"].into_iter();
+	/// let mut r = read_with::ReadWith::try_new(
+	///     || -> std::io::Result<_> { Ok(lines.next()) }
+	/// );
+	/// let mut s = String::new();
+	/// r.read_to_string(&mut s).unwrap();
+	/// assert_eq!(s, "onetwo");
+	/// ```
+	pub fn try_new(f: F) -> Self
+	{
+		ReadWith::with_generator(TryFn(f))
+	}
+}
+
+impl<I, S> ReadWith<Iter<I>, S>
+	where I: Iterator<Item=S>,
+	S: AsRef<[u8]>
+{
+	/// Create an object that will read each chunk from an iterator.
+	///
+	/// ```rust
+	/// use std::io::Read;
+	/// let mut r = read_with::ReadWith::from_iter(vec!["one", "two"]);
+	/// let mut s = String::new();
+	/// r.read_to_string(&mut s).unwrap();
+	/// assert_eq!(s, "onetwo");
+	/// ```
+	pub fn from_iter<T>(iter: T) -> Self
+		where T: IntoIterator<IntoIter=I, Item=S>
+	{
+		ReadWith::with_generator(Iter(iter.into_iter()))
+	}
+}
+
+impl<S> std::iter::FromIterator<S> for ReadWith<Iter<std::vec::IntoIter<S>>, S>
+	where S: AsRef<[u8]>
+{
+	/// Collects the chunks so they can be read.
+	///
+	/// Unlike [`from_iter`](struct.ReadWith.html#method.from_iter),
+	/// this consumes the whole iterator immediately.
+	fn from_iter<T>(iter: T) -> Self
+		where T: IntoIterator<Item=S>
+	{
+		ReadWith::from_iter(iter.into_iter().collect::<Vec<S>>())
+	}
+}
+
+/// Adds [`into_read`](#tymethod.into_read) to everything that
+/// can be iterated over.
+///
+/// ```rust
+/// use read_with::IntoRead;
+/// let mut output = vec!();
+/// std::io::copy(&mut ["one", "two"].iter().into_read(), &mut output).unwrap();
+/// assert_eq!(output, b"onetwo");
+/// ```
+pub trait IntoRead: IntoIterator + Sized
+	where Self::Item: AsRef<[u8]>
+{
+	/// Create a `ReadWith` that reads each item as a chunk.
+	fn into_read(self) -> ReadWith<Iter<Self::IntoIter>, Self::Item>;
+}
+
+impl<I> IntoRead for I
+	where I: IntoIterator,
+	I::Item: AsRef<[u8]>
+{
+	fn into_read(self) -> ReadWith<Iter<Self::IntoIter>, Self::Item>
+	{
+		ReadWith::from_iter(self)
+	}
+}
+
+impl<F, S> ReadWith<F, S>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	/// Create an object that will read from the given `Generator`.
+	pub fn with_generator(f: F) -> Self
 	{
 		ReadWith
 		{
-			f: f,
-			current: Default::default(),
+			f,
+			current: None,
 			offset: 0,
 			end: false,
+			error: None,
+			policy: ReadPolicy::FillBuffer,
+			len: None,
+			generated: 0,
+			chunks: 0,
+			consumed: 0,
+			pushed: 0,
+			front: vec!(),
+			front_offset: 0,
+		}
+	}
+
+	/// Promise that the function will produce exactly `len` bytes.
+	///
+	/// This makes [`size_hint`](#method.size_hint) exact. If the
+	/// function produces more data, reading fails with
+	/// `ErrorKind::InvalidData` at the chunk that goes past `len`,
+	/// none of which is read; if it produces less, reading fails at
+	/// the end with `ErrorKind::UnexpectedEof`. The function is not
+	/// called again, and every later read fails the same way.
+	pub fn total_len(mut self, len: u64) -> Self
+	{
+		self.len = Some(len);
+		self
+	}
+
+	/// The bounds on the number of bytes that remain to be read.
+	///
+	/// Without a [`total_len`](#method.total_len) only the rest of
+	/// the current chunk is known.
+	pub fn size_hint(&self) -> (u64, Option<u64>)
+	{
+		match self.len
+		{
+			Some(len) =>
+			{
+				let remaining = len.saturating_sub(self.consumed) + self.pushed;
+				(remaining, Some(remaining))
+			},
+			None =>
+			{
+				let front = (self.front.len()-self.front_offset) as u64;
+				if self.end
+					{ (front, Some(front)) }
+				else
+					{ (front + (self.current_chunk().len()-self.offset) as u64, None) }
+			},
+		}
+	}
+
+	/// Set how much each call to `read` tries to read.
+	pub fn read_policy(mut self, policy: ReadPolicy) -> Self
+	{
+		self.policy = policy;
+		self
+	}
+
+	/// Take ownership of the rest of the current chunk, or of the
+	/// next one from the function, without copying it.
+	///
+	/// Returns `None` at the end of the data. If the current chunk
+	/// was partially read, the bytes already read are removed from it
+	/// with [`Advance`](trait.Advance.html), which for a `Vec<u8>` moves
+	/// the rest of its bytes; chunks of `Bytes` are not copied.
+	///
+	/// Bytes given to [`unread`](#method.unread) or buffered by
+	/// [`peek`](#method.peek) don't belong to a chunk, so this fails
+	/// with `ErrorKind::InvalidInput` until they have been read.
+	///
+	/// ```rust
+	/// use std::io::Read;
+	/// let mut r = read_with::ReadWith::from_iter(vec![b"one".to_vec(), b"two".to_vec()]);
+	/// let mut buf = [0u8; 1];
+	/// r.read_exact(&mut buf).unwrap();
+	/// assert_eq!(r.next_chunk().unwrap(), Some(b"ne".to_vec()));
+	/// assert_eq!(r.next_chunk().unwrap(), Some(b"two".to_vec()));
+	/// assert_eq!(r.next_chunk().unwrap(), None);
+	/// ```
+	pub fn next_chunk(&mut self) -> std::io::Result<Option<S>>
+		where S: Advance
+	{
+		if self.front_offset < self.front.len()
+		{
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				"peeked or unread bytes must be read first",
+			));
+		}
+		self.fill_buf()?;
+		let offset = self.offset;
+		self.consumed += (self.current_chunk().len()-offset) as u64;
+		self.offset = 0;
+		Ok(self.current.take().map(|mut c| { c.advance(offset); c }))
+	}
+
+	/// Move the state to a reader of a different generator.
+	///
+	/// The unread rest of the current chunk is kept as bytes
+	/// to read first; the total length is forgotten.
+	fn replace_generator<G, T>(self, g: impl FnOnce(F) -> G)
+		-> ReadWith<G, T>
+		where G: Generator<T>,
+		T: AsRef<[u8]>
+	{
+		let mut front = self.current_chunk()[self.offset..].to_vec();
+		front.splice(..0, self.front[self.front_offset..].iter().cloned());
+		ReadWith
+		{
+			f: g(self.f),
+			current: None,
+			offset: 0,
+			end: self.end,
+			error: self.error,
+			policy: self.policy,
+			len: None,
+			generated: 0,
+			chunks: 0,
+			consumed: 0,
+			pushed: 0,
+			front,
+			front_offset: 0,
+		}
+	}
+
+	/// Look at the next `n` bytes without reading them.
+	///
+	/// If they span several chunks, they are copied to an internal
+	/// buffer. Fewer bytes are returned only at the end of the data.
+	///
+	/// ```rust
+	/// let mut r = read_with::ReadWith::from_iter(vec!["GI", "F8", "9a..."]);
+	/// assert_eq!(r.peek(4).unwrap(), b"GIF8");
+	/// assert_eq!(r.peek(16).unwrap(), b"GIF89a...");
+	/// ```
+	pub fn peek(&mut self, n: usize) -> std::io::Result<&[u8]>
+	{
+		if let Some(e) = self.error.take()
+			{ return Err(e); }
+
+		if self.front_offset == self.front.len()
+		{
+			self.front.clear();
+			self.front_offset = 0;
+			if self.fill_chunk()?.len() >= n
+				{ return Ok(&self.current_chunk()[self.offset..self.offset+n]); }
+		}
+		else
+		{
+			self.front.drain(..self.front_offset);
+			self.front_offset = 0;
+		}
+
+		while self.front.len() < n
+		{
+			let available = self.fill_chunk()?.len();
+			if available == 0
+				{ break; }
+			let take = available.min(n-self.front.len());
+			if let Some(ref c) = self.current
+				{ self.front.extend_from_slice(&c.as_ref()[self.offset..self.offset+take]); }
+			self.offset += take;
+		}
+		Ok(&self.front[..n.min(self.front.len())])
+	}
+
+	/// Put `bytes` back in front of the data, to be read next.
+	pub fn unread(&mut self, bytes: &[u8])
+	{
+		self.front.splice(..self.front_offset, bytes.iter().cloned());
+		self.front_offset = 0;
+		let back = self.consumed.min(bytes.len() as u64);
+		self.consumed -= back;
+		self.pushed += bytes.len() as u64 - back;
+	}
+
+	/// Returns the rest of the current chunk, calling the
+	/// function for a new one if it has been consumed.
+	fn fill_chunk(&mut self)
+		-> std::io::Result<&[u8]>
+	{
+		while !self.end && self.offset == self.current_chunk().len()
+			{ self.generate_chunk()?; }
+		if self.end
+		{
+			if let Some(e) = self.len_error()
+				{ return Err(e); }
+		}
+		Ok(&self.current_chunk()[self.offset..])
+	}
+
+	/// Whether the next `fill_buf` would call the function.
+	fn needs_chunk(&self) -> bool
+	{
+		self.error.is_none() && self.front_offset == self.front.len()
+			&& !self.end && self.offset == self.current_chunk().len()
+	}
+
+	/// Call the function once, replacing the current chunk.
+	fn generate_chunk(&mut self)
+		-> std::io::Result<()>
+	{
+		let n = self.f.generate()?;
+		self.offset = 0;
+		self.current = None;
+		match n
+		{
+			Some(n) =>
+			{
+				self.generated += n.as_ref().len() as u64;
+				self.chunks += 1;
+				// the chunk is dropped, and fill_chunk fails from now on
+				if self.len.is_some_and(|len| self.generated > len)
+					{ self.end = true; }
+				else
+					{ self.current = Some(n); }
+			},
+			None => self.end = true,
+		}
+		Ok(())
+	}
+
+	/// At the end of the data, the error for a function that did not
+	/// produce its total length.
+	fn len_error(&self) -> Option<std::io::Error>
+	{
+		match self.len
+		{
+			Some(len) if self.generated > len =>
+				Some(std::io::Error::new(
+					std::io::ErrorKind::InvalidData,
+					"generated more data than its total length",
+				)),
+			Some(len) if self.generated < len =>
+				Some(std::io::Error::new(
+					std::io::ErrorKind::UnexpectedEof,
+					"generated less data than its total length",
+				)),
+			_ => None,
+		}
+	}
+
+	/// The bytes that `fill_buf` would return without calling the function.
+	fn buffered(&self) -> &[u8]
+	{
+		if self.front_offset < self.front.len()
+			{ &self.front[self.front_offset..] }
+		else
+			{ &self.current_chunk()[self.offset..] }
+	}
+
+	/// Whether all of the data has been read.
+	fn at_end(&self) -> bool
+	{
+		self.error.is_none() && self.front_offset == self.front.len()
+			&& self.end && self.offset == self.current_chunk().len()
+			&& self.len.is_none_or(|len| self.generated == len)
+	}
+
+	fn current_chunk(&self) -> &[u8]
+	{
+		match self.current
+		{
+			Some(ref c) => c.as_ref(),
+			None => &[],
 		}
 	}
 }
 
 impl<F,S> Read for ReadWith<F, S>
-	where F: FnMut() -> Option<S>,
-	S: AsRef<[u8]> + Default
+	where F: Generator<S>,
+	S: AsRef<[u8]>
 {
 	fn read(&mut self, buf: &mut [u8])
 		-> std::io::Result<usize>
 	{
+		self.read_vectored(&mut [IoSliceMut::new(buf)])
+	}
+
+	/// Fills each of `bufs` in turn, reading across chunks
+	/// as `read` does.
+	fn read_vectored(&mut self, bufs: &mut [IoSliceMut])
+		-> std::io::Result<usize>
+	{
+		if let Some(e) = self.error.take()
+			{ return Err(e); }
+
 		let mut wrote = 0;
-		while !self.end && wrote < buf.len()
-		{
-			let count = (buf.len()-wrote).min(self.current.as_ref().len()-self.offset);
-			buf[wrote..wrote+count]
-				.copy_from_slice( &self.current.as_ref()[self.offset..self.offset+count] );
-			wrote += count;
-			self.offset += count;
-			if self.offset == self.current.as_ref().len()
+		'bufs: for buf in bufs.iter_mut()
+		{
+			let mut pos = 0;
+			while pos < buf.len()
 			{
-				self.offset = 0;
-				let n = (self.f)();
-				if let Some(n) = n
-					{ self.current = n; }
-				else
-					{ self.end = true; }
+				if self.policy == ReadPolicy::OneChunk
+					&& wrote > 0 && self.offset == self.current_chunk().len()
+					{ break 'bufs; }
+
+				// only ask for another chunk when there is room for it
+				let count = match self.fill_buf()
+				{
+					Ok(next) =>
+					{
+						let count = (buf.len()-pos).min(next.len());
+						buf[pos..pos+count].copy_from_slice(&next[..count]);
+						count
+					},
+					Err(e) =>
+					{
+						if wrote == 0
+							{ return Err(e); }
+						if self.policy != ReadPolicy::UntilWouldBlock
+							|| e.kind() != std::io::ErrorKind::WouldBlock
+							{ self.error = Some(e); }
+						break 'bufs;
+					},
+				};
+				if count == 0
+					{ break 'bufs; }
+				self.consume(count);
+				pos += count;
+				wrote += count;
 			}
 		}
 
 		Ok(wrote)
 	}
+
+	/// Appends each chunk to `buf` whole.
+	fn read_to_end(&mut self, buf: &mut Vec<u8>)
+		-> std::io::Result<usize>
+	{
+		let start = buf.len();
+		loop
+		{
+			let len = match self.fill_buf()
+			{
+				Ok(next) =>
+				{
+					buf.extend_from_slice(next);
+					next.len()
+				},
+				Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			};
+			if len == 0
+				{ break; }
+			self.consume(len);
+		}
+		Ok(buf.len()-start)
+	}
+
+	/// Appends each chunk to `buf` whole, checking that it is UTF-8
+	/// as it goes. A character may be split between two chunks.
+	fn read_to_string(&mut self, buf: &mut String)
+		-> std::io::Result<usize>
+	{
+		fn invalid() -> std::io::Error
+		{
+			std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				"stream did not contain valid UTF-8",
+			)
+		}
+
+		let start = buf.len();
+		// the start of a character that continues in the next chunk
+		let mut partial = vec!();
+		loop
+		{
+			let r = match self.fill_buf()
+			{
+				Ok(next) =>
+				{
+					let mut taken = 0;
+					while !partial.is_empty() && taken < next.len()
+					{
+						partial.push(next[taken]);
+						taken += 1;
+						match std::str::from_utf8(&partial)
+						{
+							Ok(c) => { buf.push_str(c); partial.clear(); },
+							Err(ref e) if e.error_len().is_none() => {},
+							Err(_) => { buf.truncate(start); return Err(invalid()); },
+						}
+					}
+
+					let rest = &next[taken..];
+					match std::str::from_utf8(rest)
+					{
+						Ok(s) => buf.push_str(s),
+						Err(ref e) if e.error_len().is_none() =>
+						{
+							let valid = e.valid_up_to();
+							buf.push_str(std::str::from_utf8(&rest[..valid]).unwrap());
+							partial.extend_from_slice(&rest[valid..]);
+						},
+						Err(_) => { buf.truncate(start); return Err(invalid()); },
+					}
+					Ok(next.len())
+				},
+				Err(e) => Err(e),
+			};
+			match r
+			{
+				Ok(0) => break,
+				Ok(len) => self.consume(len),
+				Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+				Err(e) => return Err(e),
+			}
+		}
+		if !partial.is_empty()
+		{
+			buf.truncate(start);
+			return Err(invalid());
+		}
+		Ok(buf.len()-start)
+	}
+
+	fn read_exact(&mut self, mut buf: &mut [u8])
+		-> std::io::Result<()>
+	{
+		while !buf.is_empty()
+		{
+			let count = match self.fill_buf()
+			{
+				Ok(next) =>
+				{
+					if next.is_empty()
+					{
+						return Err(std::io::Error::new(
+							std::io::ErrorKind::UnexpectedEof,
+							"failed to fill whole buffer",
+						));
+					}
+					let count = buf.len().min(next.len());
+					buf[..count].copy_from_slice(&next[..count]);
+					count
+				},
+				Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			};
+			self.consume(count);
+			buf = &mut buf[count..];
+		}
+		Ok(())
+	}
 }
 
+impl<F,S> BufRead for ReadWith<F, S>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	/// Returns the rest of the current chunk, calling the
+	/// function for a new one if it has been consumed, or
+	/// the bytes that were peeked or unread.
+	fn fill_buf(&mut self)
+		-> std::io::Result<&[u8]>
+	{
+		if let Some(e) = self.error.take()
+			{ return Err(e); }
+
+		if self.front_offset < self.front.len()
+			{ return Ok(&self.front[self.front_offset..]); }
+		self.fill_chunk()
+	}
+
+	fn consume(&mut self, amt: usize)
+	{
+		if self.front_offset < self.front.len()
+		{
+			let amt = amt.min(self.front.len()-self.front_offset);
+			self.front_offset += amt;
+			let pushed = self.pushed.min(amt as u64);
+			self.pushed -= pushed;
+			self.consumed += amt as u64 - pushed;
+			if self.front_offset == self.front.len()
+			{
+				self.front.clear();
+				self.front_offset = 0;
+			}
+			return;
+		}
+
+		let amt = amt.min(self.current_chunk().len()-self.offset);
+		self.offset += amt;
+		self.consumed += amt as u64;
+	}
+}
 
 #[cfg(test)]
 mod tests
@@ -135,4 +906,269 @@ mod tests
 		).unwrap();
 		assert_eq!("one\ntwo\nthree\n", ::std::str::from_utf8(&output).unwrap());
 	}
+
+	#[test]
+	fn errors()
+	{
+		use std::io::{Read, Error, ErrorKind};
+		let mut calls = 0;
+		let mut r = ReadWith::try_new(
+			||
+			{
+				calls += 1;
+				match calls
+				{
+					1 => Ok(Some("abc")),
+					2 => Err(Error::other("failed")),
+					3 => Ok(Some("def")),
+					_ => Ok(None),
+				}
+			}
+		);
+		let mut buf = [0u8; 16];
+		assert_eq!(r.read(&mut buf).unwrap(), 3);
+		assert_eq!(&buf[..3], b"abc");
+		assert_eq!(r.read(&mut buf).unwrap_err().kind(), ErrorKind::Other);
+		assert_eq!(r.read(&mut buf).unwrap(), 3);
+		assert_eq!(&buf[..3], b"def");
+		assert_eq!(r.read(&mut buf).unwrap(), 0);
+	}
+
+	#[test]
+	fn lazy()
+	{
+		use std::io::Read;
+		use std::cell::Cell;
+		let calls = Cell::new(0);
+		let mut many_strings = vec!["one", "two", "three"].into_iter();
+		let mut r = ReadWith::new(|| { calls.set(calls.get()+1); many_strings.next() });
+		let mut buf = [0u8; 6];
+		assert_eq!(r.read(&mut buf).unwrap(), 6);
+		assert_eq!(&buf, b"onetwo");
+		assert_eq!(calls.get(), 2);
+		assert_eq!(r.read(&mut buf[..5]).unwrap(), 5);
+		assert_eq!(calls.get(), 3);
+		assert_eq!(r.read(&mut buf).unwrap(), 0);
+		assert_eq!(calls.get(), 4);
+	}
+
+	#[test]
+	fn policies()
+	{
+		use std::io::{Read, Error, ErrorKind};
+		use ReadPolicy;
+		let mut many_strings = vec!["one", "two", "three"].into_iter();
+		let mut r = ReadWith::new(|| many_strings.next())
+			.read_policy(ReadPolicy::OneChunk);
+		let mut buf = [0u8; 16];
+		assert_eq!(r.read(&mut buf).unwrap(), 3);
+		assert_eq!(r.read(&mut buf[..2]).unwrap(), 2);
+		assert_eq!(r.read(&mut buf).unwrap(), 1);
+		assert_eq!(r.read(&mut buf).unwrap(), 5);
+		assert_eq!(r.read(&mut buf).unwrap(), 0);
+
+		let mut calls = 0;
+		let mut r = ReadWith::try_new(
+			||
+			{
+				calls += 1;
+				match calls
+				{
+					1 | 2 => Ok(Some("ab")),
+					3 => Err(Error::from(ErrorKind::WouldBlock)),
+					4 => Err(Error::from(ErrorKind::WouldBlock)),
+					5 => Ok(Some("cd")),
+					_ => Ok(None),
+				}
+			}
+		).read_policy(ReadPolicy::UntilWouldBlock);
+		assert_eq!(r.read(&mut buf).unwrap(), 4);
+		assert_eq!(r.read(&mut buf).unwrap_err().kind(), ErrorKind::WouldBlock);
+		assert_eq!(r.read(&mut buf).unwrap(), 2);
+		assert_eq!(&buf[..2], b"cd");
+	}
+
+	#[test]
+	fn chunks()
+	{
+		use std::io::Read;
+		let mut many_strings = vec![&b"one"[..], b"", b"two", b"three"].into_iter();
+		let mut r = ReadWith::new(|| many_strings.next());
+		let mut buf = [0u8; 5];
+		assert_eq!(r.next_chunk().unwrap(), Some(&b"one"[..]));
+		assert_eq!(r.read(&mut buf).unwrap(), 5);
+		assert_eq!(&buf, b"twoth");
+		assert_eq!(r.next_chunk().unwrap(), Some(&b"ree"[..]));
+		assert_eq!(r.next_chunk().unwrap(), None);
+		assert_eq!(r.read(&mut buf).unwrap(), 0);
+	}
+
+	#[cfg(feature = "bytes")]
+	#[test]
+	fn bytes()
+	{
+		use bytes::Bytes;
+		use std::io::Read;
+		let one = Bytes::from(b"one".to_vec());
+		let start = one.as_ptr();
+		let mut r = ReadWith::from_iter(vec![one, Bytes::from_static(b"two")]);
+		let mut buf = [0u8; 1];
+		r.read_exact(&mut buf).unwrap();
+		let ne = r.next_chunk().unwrap().unwrap();
+		assert_eq!(ne, "ne");
+		// the same memory, not a copy
+		assert_eq!(ne.as_ptr(), start.wrapping_add(1));
+	}
+
+	#[test]
+	fn vectored()
+	{
+		use std::io::{Read, IoSliceMut};
+		let mut many_strings = vec!["ab", "cdef", "", "ghijk", "lm"].into_iter();
+		let mut r = ReadWith::new(|| many_strings.next());
+		let mut header = [0u8; 3];
+		let mut body = [0u8; 7];
+		assert_eq!(
+			r.read_vectored(
+				&mut [IoSliceMut::new(&mut header), IoSliceMut::new(&mut []), IoSliceMut::new(&mut body)]
+			).unwrap(),
+			10,
+		);
+		assert_eq!(&header, b"abc");
+		assert_eq!(&body, b"defghij");
+		let mut rest = String::new();
+		r.read_to_string(&mut rest).unwrap();
+		assert_eq!(rest, "klm");
+	}
+
+	#[test]
+	fn to_end()
+	{
+		use std::io::{Read, ErrorKind};
+		let chunks = vec![&b"a\xc3"[..], b"", b"\xa9\xe2\x82", b"\xac!"];
+		let mut s = String::from("<");
+		assert_eq!(ReadWith::from_iter(chunks.clone()).read_to_string(&mut s).unwrap(), 7);
+		assert_eq!(s, "<a\u{e9}\u{20ac}!");
+
+		let mut v = vec!();
+		assert_eq!(ReadWith::from_iter(chunks.clone()).read_to_end(&mut v).unwrap(), 7);
+		assert_eq!(v, "a\u{e9}\u{20ac}!".as_bytes());
+
+		let mut s = String::from("<");
+		let e = ReadWith::from_iter(vec![&b"a\xc3"[..], b"a"]).read_to_string(&mut s).unwrap_err();
+		assert_eq!(e.kind(), ErrorKind::InvalidData);
+		assert_eq!(s, "<");
+		let e = ReadWith::from_iter(vec![&b"a\xc3"[..]]).read_to_string(&mut s).unwrap_err();
+		assert_eq!(e.kind(), ErrorKind::InvalidData);
+
+		let mut r = ReadWith::from_iter(chunks);
+		let mut buf = [0u8; 4];
+		r.read_exact(&mut buf).unwrap();
+		assert_eq!(&buf, b"a\xc3\xa9\xe2");
+		assert_eq!(r.read_exact(&mut buf).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+	}
+
+	#[test]
+	fn total_len()
+	{
+		use std::io::{Read, ErrorKind};
+		let mut r = ReadWith::from_iter(vec!["one", "two", "three"]).total_len(11);
+		assert_eq!(r.size_hint(), (11, Some(11)));
+		let mut buf = [0u8; 4];
+		r.read_exact(&mut buf).unwrap();
+		assert_eq!(r.size_hint(), (7, Some(7)));
+		let mut rest = String::new();
+		r.read_to_string(&mut rest).unwrap();
+		assert_eq!(rest, "wothree");
+		assert_eq!(r.size_hint(), (0, Some(0)));
+
+		let mut r = ReadWith::from_iter(vec!["one", "two", "three"]).total_len(12);
+		let mut all = vec!();
+		assert_eq!(r.read_to_end(&mut all).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+		assert_eq!(all, b"onetwothree");
+		assert_eq!(r.read_to_end(&mut all).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+		let mut calls = 0;
+		let mut chunks = vec!["one", "two", "three"].into_iter();
+		let mut r = ReadWith::new(|| { calls += 1; chunks.next() }).total_len(5);
+		let mut buf = [0u8; 16];
+		assert_eq!(r.read(&mut buf).unwrap(), 3);
+		assert_eq!(r.read(&mut buf).unwrap_err().kind(), ErrorKind::InvalidData);
+		// the error doesn't turn into the end of the data
+		assert_eq!(r.read(&mut buf).unwrap_err().kind(), ErrorKind::InvalidData);
+		assert_eq!(r.read_to_end(&mut vec!()).unwrap_err().kind(), ErrorKind::InvalidData);
+		drop(r);
+		assert_eq!(calls, 2);
+
+		let mut r = ReadWith::from_iter(vec!["one", "two"]);
+		assert_eq!(r.size_hint(), (0, None));
+		r.read_exact(&mut buf[..1]).unwrap();
+		assert_eq!(r.size_hint(), (2, None));
+	}
+
+	#[test]
+	fn peek()
+	{
+		use std::io::Read;
+		let mut r = ReadWith::from_iter(vec!["one", "", "two", "three"]);
+		assert_eq!(r.peek(2).unwrap(), b"on");
+		assert_eq!(r.peek(5).unwrap(), b"onetw");
+		let mut buf = [0u8; 2];
+		r.read_exact(&mut buf).unwrap();
+		assert_eq!(&buf, b"on");
+		assert_eq!(r.peek(4).unwrap(), b"etwo");
+		r.unread(b"<<");
+		assert_eq!(r.size_hint(), (6, None));
+		let mut s = String::new();
+		r.read_to_string(&mut s).unwrap();
+		assert_eq!(s, "<<etwothree");
+		assert_eq!(r.peek(1).unwrap(), b"");
+		r.unread(b"!");
+		assert_eq!(r.size_hint(), (1, Some(1)));
+		s.clear();
+		r.read_to_string(&mut s).unwrap();
+		assert_eq!(s, "!");
+
+		// more bytes than were read can be put back
+		let mut r = ReadWith::from_iter(vec!["abc", "def"]).total_len(6);
+		r.unread(b"<<");
+		assert_eq!(r.size_hint(), (8, Some(8)));
+		r.read_exact(&mut buf).unwrap();
+		assert_eq!(r.size_hint(), (6, Some(6)));
+		r.read_exact(&mut buf).unwrap();
+		r.unread(b"[[[");
+		assert_eq!(r.size_hint(), (7, Some(7)));
+		s.clear();
+		assert_eq!(r.read_to_string(&mut s).unwrap(), 7);
+		assert_eq!(s, "[[[cdef");
+		assert_eq!(r.size_hint(), (0, Some(0)));
+	}
+
+	#[test]
+	fn iterators()
+	{
+		use IntoRead;
+		use std::io::Read;
+		let many_strings = ["one", "two", "three"];
+		let mut s = String::new();
+		many_strings.iter().into_read().read_to_string(&mut s).unwrap();
+		assert_eq!("onetwothree", s);
+
+		let mut r: ReadWith<_, _> = many_strings.iter()
+			.map(|o| o.to_string() + "\n")
+			.collect();
+		s.clear();
+		r.read_to_string(&mut s).unwrap();
+		assert_eq!("one\ntwo\nthree\n", s);
+	}
+
+	#[test]
+	fn lines()
+	{
+		use std::io::BufRead;
+		let mut many_strings = vec!["one\ntw", "", "o\nthr", "ee"].into_iter();
+		let r = ReadWith::new(|| many_strings.next());
+		let lines: Vec<String> = r.lines().map(|l| l.unwrap()).collect();
+		assert_eq!(lines, ["one", "two", "three"]);
+	}
 }
@@ -0,0 +1,238 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use ::{Generator, ReadWith};
+
+struct State<S>
+{
+	/// The index of the next chunk to compute
+	next: usize,
+	/// The index of the next chunk to read
+	cursor: usize,
+	/// The chunks computed ahead of the cursor
+	done: BTreeMap<usize, Result<Option<S>, Box<dyn Any + Send>>>,
+	/// The first index for which the function ended or panicked
+	end: Option<usize>,
+	dropped: bool,
+}
+
+struct Shared<S>
+{
+	state: Mutex<State<S>>,
+	changed: Condvar,
+}
+
+impl<S> Shared<S>
+{
+	fn lock(&self) -> MutexGuard<'_, State<S>>
+	{
+		// a panic in the function is never raised while this is held,
+		// except in the reader, which has taken its chunk by then
+		self.state.lock().unwrap_or_else(|e| e.into_inner())
+	}
+}
+
+/// A `Generator` that computes the chunks of an indexed
+/// function on several threads, and yields them in order
+///
+/// Created by [`ReadWith::parallel`](struct.ReadWith.html#method.parallel).
+/// When it is dropped, the workers stop after their current
+/// chunks and are joined.
+pub struct Parallel<S>
+{
+	shared: Arc<Shared<S>>,
+	workers: Vec<JoinHandle<()>>,
+}
+
+/// A `ReadWith` whose chunks are computed on several threads
+pub type ParallelReadWith<S> = ReadWith<Parallel<S>, S>;
+
+impl<S> Generator<S> for Parallel<S>
+{
+	fn generate(&mut self) -> std::io::Result<Option<S>>
+	{
+		let mut state = self.shared.lock();
+		loop
+		{
+			let cursor = state.cursor;
+			if let Some(c) = state.done.remove(&cursor)
+			{
+				state.cursor += 1;
+				drop(state);
+				self.shared.changed.notify_all();
+				match c
+				{
+					Ok(c) => return Ok(c),
+					Err(p) => panic::resume_unwind(p),
+				}
+			}
+			if matches!(state.end, Some(end) if end < cursor)
+				{ return Ok(None); }
+			state = self.shared.changed.wait(state)
+				.unwrap_or_else(|e| e.into_inner());
+		}
+	}
+}
+
+impl<S> Drop for Parallel<S>
+{
+	fn drop(&mut self)
+	{
+		self.shared.lock().dropped = true;
+		self.shared.changed.notify_all();
+		for worker in self.workers.drain(..)
+			{ let _ = worker.join(); }
+	}
+}
+
+fn work<F, S>(f: &F, shared: &Shared<S>, window: usize)
+	where F: Fn(usize) -> Option<S>
+{
+	let mut state = shared.lock();
+	loop
+	{
+		if state.dropped || matches!(state.end, Some(end) if end <= state.next)
+			{ return; }
+		if state.next - state.cursor >= window
+		{
+			state = shared.changed.wait(state)
+				.unwrap_or_else(|e| e.into_inner());
+			continue;
+		}
+		let i = state.next;
+		state.next += 1;
+		drop(state);
+
+		let c = panic::catch_unwind(AssertUnwindSafe(|| f(i)));
+
+		state = shared.lock();
+		if !matches!(c, Ok(Some(_))) && state.end.is_none_or(|end| i < end)
+			{ state.end = Some(i); }
+		state.done.insert(i, c);
+		shared.changed.notify_all();
+	}
+}
+
+impl<S> ReadWith<Parallel<S>, S>
+	where S: AsRef<[u8]> + Send + 'static
+{
+	/// Create an object that will read `f(0)`, `f(1)` and so on, until
+	/// `f` returns `None`, with the calls made on `threads` threads.
+	///
+	/// At most `window` chunks past the one being read are computed
+	/// or held at once. A panic in `f` is resumed in the reading thread
+	/// when it reaches that chunk.
+	///
+	/// Panics if `threads` or `window` is zero.
+	///
+	/// ```rust
+	/// use std::io::Read;
+	/// let mut r = read_with::ReadWith::parallel(
+	///     |i| if i < 100 { Some(format!("{} ", i * i)) } else { None },
+	///     4,
+	///     16,
+	/// );
+	/// let mut s = String::new();
+	/// r.read_to_string(&mut s).unwrap();
+	/// assert!(s.starts_with("0 1 4 9 16 "));
+	/// ```
+	pub fn parallel<F>(f: F, threads: usize, window: usize) -> Self
+		where F: Fn(usize) -> Option<S> + Send + Sync + 'static
+	{
+		assert!(threads > 0, "number of threads must not be zero");
+		assert!(window > 0, "window must not be zero");
+		let f = Arc::new(f);
+		let shared = Arc::new(
+			Shared
+			{
+				state: Mutex::new(
+					State
+					{
+						next: 0,
+						cursor: 0,
+						done: BTreeMap::new(),
+						end: None,
+						dropped: false,
+					}
+				),
+				changed: Condvar::new(),
+			}
+		);
+		let workers = (0..threads)
+			.map(
+				|_|
+				{
+					let f = f.clone();
+					let shared = shared.clone();
+					thread::spawn(move || work(&*f, &shared, window))
+				}
+			)
+			.collect();
+		ReadWith::with_generator(Parallel { shared, workers })
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::ReadWith;
+	use std::io::{Read, BufRead};
+	use std::sync::mpsc;
+	use std::thread::sleep;
+	use std::time::Duration;
+
+	#[test]
+	fn ordered()
+	{
+		let mut r = ReadWith::parallel(
+			|i|
+			{
+				// later chunks are often done first
+				sleep(Duration::from_millis((7 - i as u64 % 7) * 2));
+				if i < 30 { Some(vec![i as u8]) } else { None }
+			},
+			5,
+			8,
+		);
+		let mut data = vec!();
+		r.read_to_end(&mut data).unwrap();
+		assert_eq!(data, (0..30).collect::<Vec<u8>>());
+		assert_eq!(r.read(&mut [0u8; 4]).unwrap(), 0);
+	}
+
+	#[test]
+	fn window()
+	{
+		let (called, calls) = mpsc::channel();
+		let mut r = ReadWith::parallel(
+			move |i|
+			{
+				called.send(i).unwrap();
+				if i == 6
+					{ panic!("chunk failed"); }
+				Some("ab")
+			},
+			3,
+			4,
+		);
+		assert_eq!(r.fill_buf().unwrap(), b"ab");
+		// the chunk being read and the 4 after it
+		let mut started: Vec<usize> = calls.iter().take(5).collect();
+		started.sort();
+		assert_eq!(started, [0, 1, 2, 3, 4]);
+		assert_eq!(calls.try_iter().count(), 0);
+
+		let mut buf = [0u8; 12];
+		r.read_exact(&mut buf).unwrap();
+		let p = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| r.read(&mut buf)))
+			.unwrap_err();
+		assert_eq!(p.downcast_ref::<&str>(), Some(&"chunk failed"));
+		drop(r);
+		// up to 4 chunks after the cursor may have started before
+		// the panic; the workers have stopped, dropping the function
+		let stopped = 5 + calls.iter().count();
+		assert!((7..=10).contains(&stopped));
+	}
+}
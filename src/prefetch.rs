@@ -0,0 +1,161 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::thread::{self, JoinHandle};
+use ::{Generator, ReadWith};
+
+enum Message<S>
+{
+	Chunk(std::io::Result<Option<S>>),
+	Panic(Box<dyn Any + Send>),
+}
+
+/// A `Generator` that receives the chunks of another,
+/// which runs on a worker thread
+///
+/// Created by [`ReadWith::prefetch`](struct.ReadWith.html#method.prefetch).
+/// When it is dropped, the worker stops after its current
+/// chunk and is joined.
+pub struct Prefetch<S>
+{
+	rx: Option<Receiver<Message<S>>>,
+	worker: Option<JoinHandle<()>>,
+}
+
+/// A `ReadWith` whose generator runs on a worker thread
+pub type PrefetchReadWith<S> = ReadWith<Prefetch<S>, S>;
+
+impl<S> Generator<S> for Prefetch<S>
+{
+	fn generate(&mut self) -> std::io::Result<Option<S>>
+	{
+		let rx = match self.rx
+		{
+			Some(ref rx) => rx,
+			None => return Ok(None),
+		};
+		match rx.recv()
+		{
+			Ok(Message::Chunk(c)) => c,
+			Ok(Message::Panic(p)) =>
+			{
+				self.rx = None;
+				panic::resume_unwind(p);
+			},
+			// the worker has stopped after the last chunk
+			Err(_) => Ok(None),
+		}
+	}
+}
+
+impl<S> Drop for Prefetch<S>
+{
+	fn drop(&mut self)
+	{
+		// the worker fails to send its next chunk, and stops
+		self.rx = None;
+		if let Some(worker) = self.worker.take()
+			{ let _ = worker.join(); }
+	}
+}
+
+impl<S> ReadWith<Prefetch<S>, S>
+	where S: AsRef<[u8]> + Send + 'static
+{
+	/// Create an object that will read from a function that runs
+	/// on another thread, up to `chunks` chunks ahead of the reader.
+	///
+	/// Errors from the function are returned by `read` in order,
+	/// and a panic in the function is resumed in the reading thread.
+	///
+	/// ```rust
+	/// use std::io::Read;
+	/// let mut many_strings = vec!["one", "two", "three"].into_iter();
+	/// let mut r = read_with::ReadWith::prefetch(move || many_strings.next(), 2);
+	/// let mut s = String::new();
+	/// r.read_to_string(&mut s).unwrap();
+	/// assert_eq!(s, "onetwothree");
+	/// ```
+	pub fn prefetch<F>(mut f: F, chunks: usize) -> Self
+		where F: Generator<S> + Send + 'static
+	{
+		let (tx, rx) = sync_channel(chunks);
+		let worker = thread::spawn(
+			move ||
+			{
+				loop
+				{
+					let message = match panic::catch_unwind(AssertUnwindSafe(|| f.generate()))
+					{
+						Ok(c) => Message::Chunk(c),
+						Err(p) => Message::Panic(p),
+					};
+					let last = matches!(message, Message::Chunk(Ok(None)) | Message::Panic(_));
+					if tx.send(message).is_err() || last
+						{ return; }
+				}
+			}
+		);
+		ReadWith::with_generator(Prefetch { rx: Some(rx), worker: Some(worker) })
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::{ReadWith, TryFn};
+	use std::io::{Read, BufRead, Error, ErrorKind};
+	use std::sync::mpsc;
+
+	#[test]
+	fn bounded()
+	{
+		let (called, calls) = mpsc::channel();
+		let mut n = 0;
+		let mut r = ReadWith::prefetch(
+			TryFn(
+				move ||
+				{
+					called.send(n).unwrap();
+					n += 1;
+					if n == 2
+						{ Err(Error::other("failed")) }
+					else
+						{ Ok(Some((n-1).to_string().into_bytes())) }
+				}
+			),
+			1,
+		);
+		assert_eq!(r.fill_buf().unwrap(), b"0");
+		r.consume(1);
+		assert_eq!(r.fill_buf().unwrap_err().kind(), ErrorKind::Other);
+		assert_eq!(r.fill_buf().unwrap(), b"2");
+		// one chunk in the channel, and one waiting to be sent
+		assert_eq!(calls.iter().take(5).last(), Some(4));
+		assert_eq!(calls.try_iter().count(), 0);
+		drop(r);
+		// the worker has stopped, dropping the function
+		assert_eq!(calls.iter().count(), 0);
+	}
+
+	#[test]
+	fn panics()
+	{
+		let mut n = 0;
+		let mut r = ReadWith::prefetch(
+			move ||
+			{
+				n += 1;
+				if n == 3
+					{ panic!("generator failed"); }
+				Some("ab")
+			},
+			4,
+		);
+		let mut buf = [0u8; 4];
+		r.read_exact(&mut buf).unwrap();
+		let p = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| r.read(&mut buf)))
+			.unwrap_err();
+		assert_eq!(p.downcast_ref::<&str>(), Some(&"generator failed"));
+	}
+}
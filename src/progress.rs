@@ -0,0 +1,263 @@
+use std::io::{Read, BufRead, IoSliceMut};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use ::{Generator, ReadWith};
+
+/// Receives the progress of an [`Observed`](struct.Observed.html) reader
+///
+/// Implemented for every `FnMut(u64, u64)`, which is
+/// given the totals passed to `update`.
+pub trait Progress
+{
+	/// Called whenever more bytes were read or chunks were taken from
+	/// the generator, with the totals since observing began.
+	fn update(&mut self, bytes: u64, chunks: u64);
+
+	/// Called once, when all of the data has been read.
+	fn end(&mut self)
+	{
+	}
+}
+
+impl<F> Progress for F
+	where F: FnMut(u64, u64)
+{
+	fn update(&mut self, bytes: u64, chunks: u64)
+	{
+		self(bytes, chunks)
+	}
+}
+
+/// A `Progress` that stores the totals where other
+/// threads can see them
+///
+/// Clones share the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct Counters
+{
+	/// The bytes read.
+	pub bytes: Arc<AtomicU64>,
+	/// The chunks taken from the generator.
+	pub chunks: Arc<AtomicU64>,
+	/// Whether all of the data has been read.
+	pub done: Arc<AtomicBool>,
+}
+
+impl Progress for Counters
+{
+	fn update(&mut self, bytes: u64, chunks: u64)
+	{
+		self.bytes.store(bytes, Ordering::Relaxed);
+		self.chunks.store(chunks, Ordering::Relaxed);
+	}
+
+	fn end(&mut self)
+	{
+		self.done.store(true, Ordering::Release);
+	}
+}
+
+/// A reader that reports how far a `ReadWith` has been read
+///
+/// Created by [`ReadWith::observe`](struct.ReadWith.html#method.observe).
+pub struct Observed<F, S, P>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	inner: ReadWith<F, S>,
+	progress: P,
+	/// The counts of the reader when observing began
+	start: (u64, u64),
+	last: (u64, u64),
+	ended: bool,
+}
+
+impl<F, S> ReadWith<F, S>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	/// Report the bytes read, the chunks taken from the generator and
+	/// the end of the data to `progress`, as they happen.
+	///
+	/// ```rust
+	/// use std::io::Read;
+	/// use read_with::{ReadWith, Counters};
+	/// let counters = Counters::default();
+	/// let mut r = ReadWith::from_iter(vec!["one", "two"]).observe(counters.clone());
+	/// let mut buf = [0u8; 4];
+	/// r.read_exact(&mut buf).unwrap();
+	/// assert_eq!(counters.bytes.load(std::sync::atomic::Ordering::Relaxed), 4);
+	/// assert_eq!(counters.chunks.load(std::sync::atomic::Ordering::Relaxed), 2);
+	/// ```
+	pub fn observe<P>(self, progress: P) -> Observed<F, S, P>
+		where P: Progress
+	{
+		let start = (self.consumed, self.chunks);
+		Observed
+		{
+			inner: self,
+			progress,
+			start,
+			last: (0, 0),
+			ended: false,
+		}
+	}
+}
+
+impl<F, S, P> Observed<F, S, P>
+	where F: Generator<S>,
+	S: AsRef<[u8]>,
+	P: Progress
+{
+	/// The `Progress` being reported to.
+	pub fn progress(&self) -> &P
+	{
+		&self.progress
+	}
+
+	/// Stop reporting, returning the reader.
+	pub fn into_inner(self) -> ReadWith<F, S>
+	{
+		self.inner
+	}
+
+	fn report(&mut self, ok: bool)
+	{
+		let totals = (
+			self.inner.consumed.saturating_sub(self.start.0),
+			self.inner.chunks - self.start.1,
+		);
+		if totals != self.last
+		{
+			self.last = totals;
+			self.progress.update(totals.0, totals.1);
+		}
+		if ok && !self.ended && self.inner.at_end()
+		{
+			self.ended = true;
+			self.progress.end();
+		}
+	}
+}
+
+impl<F, S, P> Read for Observed<F, S, P>
+	where F: Generator<S>,
+	S: AsRef<[u8]>,
+	P: Progress
+{
+	fn read(&mut self, buf: &mut [u8])
+		-> std::io::Result<usize>
+	{
+		let r = self.inner.read(buf);
+		self.report(r.is_ok());
+		r
+	}
+
+	fn read_vectored(&mut self, bufs: &mut [IoSliceMut])
+		-> std::io::Result<usize>
+	{
+		let r = self.inner.read_vectored(bufs);
+		self.report(r.is_ok());
+		r
+	}
+}
+
+impl<F, S, P> BufRead for Observed<F, S, P>
+	where F: Generator<S>,
+	S: AsRef<[u8]>,
+	P: Progress
+{
+	fn fill_buf(&mut self)
+		-> std::io::Result<&[u8]>
+	{
+		if let Err(e) = self.inner.fill_buf()
+		{
+			self.report(false);
+			return Err(e);
+		}
+		self.report(true);
+		// the chunk is already there
+		self.inner.fill_buf()
+	}
+
+	fn consume(&mut self, amt: usize)
+	{
+		self.inner.consume(amt);
+		self.report(true);
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::{ReadWith, Counters, Progress};
+	use std::io::{Read, BufRead, Error, ErrorKind};
+	use std::sync::atomic::Ordering;
+
+	#[derive(Default)]
+	struct Log(Vec<String>);
+
+	impl Progress for Log
+	{
+		fn update(&mut self, bytes: u64, chunks: u64)
+		{
+			self.0.push(format!("{}/{}", bytes, chunks));
+		}
+
+		fn end(&mut self)
+		{
+			self.0.push("end".to_string());
+		}
+	}
+
+	#[test]
+	fn partial_reads()
+	{
+		let mut r = ReadWith::from_iter(vec!["abc", "", "defg", "h"]).observe(Log::default());
+		let mut buf = [0u8; 2];
+		assert_eq!(r.read(&mut buf).unwrap(), 2);
+		assert_eq!(r.read(&mut buf).unwrap(), 2);
+		assert_eq!(r.fill_buf().unwrap(), b"efg");
+		r.consume(1);
+		let mut rest = vec!();
+		r.read_to_end(&mut rest).unwrap();
+		assert_eq!(rest, b"fgh");
+		assert_eq!(r.read(&mut buf).unwrap(), 0);
+		// the empty chunk is taken from the generator too
+		assert_eq!(r.progress().0, ["2/1", "4/3", "5/3", "8/4", "end"]);
+	}
+
+	#[test]
+	fn counters()
+	{
+		let counters = Counters::default();
+		let mut calls = 0;
+		let mut r = ReadWith::try_new(
+			move ||
+			{
+				calls += 1;
+				match calls
+				{
+					1 => Ok(Some("abcd")),
+					2 => Err(Error::other("failed")),
+					3 => Ok(Some("ef")),
+					_ => Ok(None),
+				}
+			}
+		);
+		let mut buf = [0u8; 3];
+		r.read_exact(&mut buf).unwrap();
+		let mut r = r.observe(counters.clone());
+		let mut buf = [0u8; 8];
+		assert_eq!(r.read(&mut buf).unwrap(), 1);
+		assert_eq!(r.read(&mut buf).unwrap_err().kind(), ErrorKind::Other);
+		assert_eq!(counters.bytes.load(Ordering::Relaxed), 1);
+		assert_eq!(counters.chunks.load(Ordering::Relaxed), 0);
+		assert!(!counters.done.load(Ordering::Acquire));
+		// reading on to fill the buffer finds the end
+		assert_eq!(r.read(&mut buf).unwrap(), 2);
+		assert_eq!(counters.bytes.load(Ordering::Relaxed), 3);
+		assert_eq!(counters.chunks.load(Ordering::Relaxed), 1);
+		assert!(counters.done.load(Ordering::Acquire));
+	}
+}
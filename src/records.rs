@@ -0,0 +1,320 @@
+use std::cell::Cell;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use serde::Serialize;
+use ::{Generator, ReadWith};
+
+/// The layout of the records from
+/// [`ReadWith::records`](struct.ReadWith.html#method.records)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat
+{
+	/// One JSON record per line, as in JSON Lines (NDJSON).
+	JsonLines,
+	/// A JSON array of the records, `[a,b,c]`.
+	JsonArray,
+	/// One CSV record per line, with its values quoted where needed.
+	Csv
+	{
+		/// Whether to start with a line of the field names of
+		/// the first record, which is left out if there are none.
+		header: bool,
+	},
+}
+
+/// A serialized record, as a chunk of a
+/// [`RecordsReadWith`](type.RecordsReadWith.html)
+///
+/// Its buffer is used again for a later record once it has been read.
+pub struct Record
+{
+	buf: Vec<u8>,
+	spare: Arc<Mutex<Vec<u8>>>,
+}
+
+impl AsRef<[u8]> for Record
+{
+	fn as_ref(&self) -> &[u8]
+	{
+		&self.buf
+	}
+}
+
+impl Drop for Record
+{
+	fn drop(&mut self)
+	{
+		let mut buf = std::mem::take(&mut self.buf);
+		buf.clear();
+		if let Ok(mut spare) = self.spare.lock()
+			{ *spare = buf; }
+	}
+}
+
+/// Where the CSV writer puts each record, to be swapped out of it
+struct Out(Cell<Vec<u8>>);
+
+impl Write for Out
+{
+	fn write(&mut self, data: &[u8]) -> std::io::Result<usize>
+	{
+		self.0.get_mut().extend_from_slice(data);
+		Ok(data.len())
+	}
+
+	fn flush(&mut self) -> std::io::Result<()>
+	{
+		Ok(())
+	}
+}
+
+/// A `Generator` of the serialized items of an iterator
+///
+/// Created by [`ReadWith::records`](struct.ReadWith.html#method.records).
+pub struct Records<I>
+{
+	iter: I,
+	format: RecordFormat,
+	first: bool,
+	end: bool,
+	spare: Arc<Mutex<Vec<u8>>>,
+	csv: Option<csv::Writer<Out>>,
+}
+
+/// A `ReadWith` of serialized records
+pub type RecordsReadWith<I> = ReadWith<Records<I>, Record>;
+
+impl<I> Records<I>
+{
+	fn write_csv<T>(&mut self, header: bool, item: &T, buf: &mut Vec<u8>)
+		-> std::io::Result<()>
+		where T: Serialize
+	{
+		let first = self.first;
+		let csv = self.csv.get_or_insert_with(
+			|| csv::WriterBuilder::new()
+				.has_headers(header && first)
+				.from_writer(Out(Cell::new(vec!())))
+		);
+		match csv.serialize(item).map_err(std::io::Error::from).and_then(|()| csv.flush())
+		{
+			Ok(()) =>
+			{
+				*buf = csv.get_ref().0.replace(std::mem::take(buf));
+				Ok(())
+			},
+			Err(e) =>
+			{
+				// part of the record may be in the writer
+				self.csv = None;
+				Err(e)
+			},
+		}
+	}
+}
+
+impl<I> Generator<Record> for Records<I>
+	where I: Iterator,
+	I::Item: Serialize
+{
+	fn generate(&mut self) -> std::io::Result<Option<Record>>
+	{
+		if self.end
+			{ return Ok(None); }
+
+		let mut buf = self.spare.lock()
+			.map(|mut spare| std::mem::take(&mut *spare))
+			.unwrap_or_default();
+		let item = match self.iter.next()
+		{
+			Some(item) => item,
+			None =>
+			{
+				self.end = true;
+				if self.format != RecordFormat::JsonArray
+					{ return Ok(None); }
+				if self.first
+					{ buf.push(b'['); }
+				buf.push(b']');
+				return Ok(Some(Record { buf, spare: self.spare.clone() }));
+			},
+		};
+
+		let r = match self.format
+		{
+			RecordFormat::JsonLines =>
+				serde_json::to_writer(&mut buf, &item)
+					.map(|()| buf.push(b'\n'))
+					.map_err(Into::into),
+			RecordFormat::JsonArray =>
+			{
+				buf.push(if self.first { b'[' } else { b',' });
+				serde_json::to_writer(&mut buf, &item).map_err(Into::into)
+			},
+			RecordFormat::Csv { header } => self.write_csv(header, &item, &mut buf),
+		};
+		if let Err(e) = r
+		{
+			// the record is skipped, but not what comes before it
+			drop(Record { buf, spare: self.spare.clone() });
+			return Err(e);
+		}
+		self.first = false;
+		Ok(Some(Record { buf, spare: self.spare.clone() }))
+	}
+}
+
+impl<I> ReadWith<Records<I>, Record>
+	where I: Iterator,
+	I::Item: Serialize
+{
+	/// Create an object that will read the items of `iter`,
+	/// serialized in the given format.
+	///
+	/// Each item is serialized when it is needed, into one of two
+	/// buffers that are used again once their records have been read.
+	/// An item that fails to serialize is skipped, after its error
+	/// has been returned.
+	///
+	/// ```rust
+	/// extern crate serde;
+	/// extern crate read_with;
+	/// use std::io::Read;
+	/// use serde::Serialize;
+	/// use read_with::{ReadWith, RecordFormat};
+	///
+	/// #[derive(Serialize)]
+	/// struct Row { n: u32, name: &'static str }
+	///
+	/// let rows = vec![Row { n: 1, name: "one" }, Row { n: 2, name: "two, three" }];
+	/// let mut r = ReadWith::records(rows, RecordFormat::Csv { header: true });
+	/// let mut s = String::new();
+	/// r.read_to_string(&mut s).unwrap();
+	/// assert_eq!(s, "n,name\n1,one\n2,\"two, three\"\n");
+	/// ```
+	pub fn records<T>(iter: T, format: RecordFormat) -> Self
+		where T: IntoIterator<IntoIter=I, Item=I::Item>
+	{
+		ReadWith::with_generator(
+			Records
+			{
+				iter: iter.into_iter(),
+				format,
+				first: true,
+				end: false,
+				spare: Arc::new(Mutex::new(vec!())),
+				csv: None,
+			}
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::{ReadWith, RecordFormat};
+	use serde::{Serialize, Serializer};
+	use std::io::{Read, BufRead, ErrorKind};
+
+	/// Fails to serialize when empty.
+	struct Name(&'static str);
+
+	impl Serialize for Name
+	{
+		fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+			where S: Serializer
+		{
+			if self.0.is_empty()
+				{ return Err(::serde::ser::Error::custom("no name")); }
+			s.serialize_str(self.0)
+		}
+	}
+
+	#[derive(Serialize)]
+	struct Row
+	{
+		n: u32,
+		name: Name,
+	}
+
+	fn rows(names: &[&'static str]) -> Vec<Row>
+	{
+		names.iter().enumerate()
+			.map(|(n, &name)| Row { n: n as u32, name: Name(name) })
+			.collect()
+	}
+
+	fn read(names: &[&'static str], format: RecordFormat) -> String
+	{
+		let mut s = String::new();
+		ReadWith::records(rows(names), format).read_to_string(&mut s).unwrap();
+		s
+	}
+
+	#[test]
+	fn json()
+	{
+		assert_eq!(
+			read(&["one", "t\"wo"], RecordFormat::JsonLines),
+			"{\"n\":0,\"name\":\"one\"}\n{\"n\":1,\"name\":\"t\\\"wo\"}\n",
+		);
+		assert_eq!(
+			read(&["one", "two"], RecordFormat::JsonArray),
+			"[{\"n\":0,\"name\":\"one\"},{\"n\":1,\"name\":\"two\"}]",
+		);
+		assert_eq!(read(&["one"], RecordFormat::JsonArray), "[{\"n\":0,\"name\":\"one\"}]");
+		assert_eq!(read(&[], RecordFormat::JsonArray), "[]");
+		assert_eq!(read(&[], RecordFormat::JsonLines), "");
+
+		let mut r = ReadWith::records(rows(&["", "one"]), RecordFormat::JsonArray);
+		let mut buf = [0u8; 64];
+		assert_eq!(r.read(&mut buf).unwrap_err().kind(), ErrorKind::InvalidData);
+		let mut s = String::new();
+		r.read_to_string(&mut s).unwrap();
+		assert_eq!(s, "[{\"n\":1,\"name\":\"one\"}]");
+	}
+
+	#[test]
+	fn csv()
+	{
+		assert_eq!(
+			read(&["one", "a,b", "say \"hi\"", "two\nlines"], RecordFormat::Csv { header: true }),
+			"n,name\n0,one\n1,\"a,b\"\n2,\"say \"\"hi\"\"\"\n3,\"two\nlines\"\n",
+		);
+		assert_eq!(read(&["one"], RecordFormat::Csv { header: false }), "0,one\n");
+		assert_eq!(read(&[], RecordFormat::Csv { header: true }), "");
+
+		// the header comes with the first record that could be written
+		let mut r = ReadWith::records(rows(&["", "one", "", "two"]), RecordFormat::Csv { header: true });
+		let mut buf = [0u8; 64];
+		assert!(r.read(&mut buf).is_err());
+		assert_eq!(r.read(&mut buf).unwrap(), 13);
+		assert_eq!(&buf[..13], b"n,name\n1,one\n");
+		assert!(r.read(&mut buf).is_err());
+		let mut s = String::new();
+		r.read_to_string(&mut s).unwrap();
+		assert_eq!(s, "3,two\n");
+	}
+
+	#[test]
+	fn buffers()
+	{
+		let mut r = ReadWith::records(rows(&["a"; 5]), RecordFormat::JsonLines);
+		let mut starts = vec!();
+		loop
+		{
+			let len =
+			{
+				let next = r.fill_buf().unwrap();
+				starts.push(next.as_ptr());
+				next.len()
+			};
+			if len == 0
+				{ break; }
+			r.consume(len);
+		}
+		assert_eq!(starts[0], starts[2]);
+		assert_eq!(starts[1], starts[3]);
+		assert_eq!(starts[2], starts[4]);
+	}
+}
@@ -0,0 +1,379 @@
+use std::io::{Read, BufRead, Seek, SeekFrom, IoSliceMut};
+use ::{Generator, ReadWith, TryFn};
+
+/// A source of chunks at any offset, for a [`SeekWith`](struct.SeekWith.html)
+///
+/// This is implemented for every `FnMut(u64) -> Option<S>` and,
+/// through [`TryFn`](struct.TryFn.html), for every
+/// `FnMut(u64) -> Result<Option<S>, E>`.
+pub trait ChunkSource<S>
+{
+	/// Produce a chunk that starts at `pos`, `None` or an
+	/// empty chunk at the end of the data.
+	fn chunk_at(&mut self, pos: u64) -> std::io::Result<Option<S>>;
+
+	/// The total length of the data, if it is known.
+	fn len_hint(&self) -> Option<u64>
+	{
+		None
+	}
+}
+
+impl<F, S> ChunkSource<S> for F
+	where F: FnMut(u64) -> Option<S>
+{
+	fn chunk_at(&mut self, pos: u64) -> std::io::Result<Option<S>>
+	{
+		Ok(self(pos))
+	}
+}
+
+impl<F, S, E> ChunkSource<S> for TryFn<F>
+	where F: FnMut(u64) -> Result<Option<S>, E>,
+	E: Into<std::io::Error>
+{
+	fn chunk_at(&mut self, pos: u64) -> std::io::Result<Option<S>>
+	{
+		(self.0)(pos).map_err(Into::into)
+	}
+}
+
+/// A chunk cut off at the total length
+struct Part<S>
+{
+	chunk: S,
+	len: usize,
+}
+
+impl<S> AsRef<[u8]> for Part<S>
+	where S: AsRef<[u8]>
+{
+	fn as_ref(&self) -> &[u8]
+	{
+		&self.chunk.as_ref()[..self.len]
+	}
+}
+
+/// Gives a `ReadWith` the chunks of a `ChunkSource`, one after another
+struct At<F>
+{
+	f: F,
+	/// The offset just after the current chunk
+	next: u64,
+	len: Option<u64>,
+}
+
+impl<F, S> Generator<Part<S>> for At<F>
+	where F: ChunkSource<S>,
+	S: AsRef<[u8]>
+{
+	fn generate(&mut self) -> std::io::Result<Option<Part<S>>>
+	{
+		let remaining = self.len.map(|len| len.saturating_sub(self.next));
+		if remaining == Some(0)
+			{ return Ok(None); }
+
+		let chunk = match self.f.chunk_at(self.next)?
+		{
+			Some(c) => c,
+			None => return Ok(None),
+		};
+		let len = match remaining
+		{
+			Some(r) if r < chunk.as_ref().len() as u64 => r as usize,
+			_ => chunk.as_ref().len(),
+		};
+		if len == 0
+			{ return Ok(None); }
+		self.next += len as u64;
+		Ok(Some(Part { chunk, len }))
+	}
+}
+
+/// An object that implements `Read` and `Seek`
+///
+/// The [`ChunkSource`](trait.ChunkSource.html), usually a function,
+/// is given the offset of the next byte to read and returns a chunk
+/// starting at that offset, or `None` (or an empty chunk) at the end
+/// of the data. It is called again with the new offset after each
+/// seek that leaves the current chunk. Chunks are read, and errors
+/// returned, as by a [`ReadWith`](struct.ReadWith.html).
+///
+/// Example:
+///
+/// ```rust
+/// use std::io::{Read, Seek, SeekFrom};
+/// let data: Vec<u8> = (0..100).collect();
+/// let mut r = read_with::SeekWith::new(
+///     |pos| data.get(pos as usize..).map(|d| &d[..d.len().min(16)])
+/// ).total_len(100);
+/// r.seek(SeekFrom::End(-3)).unwrap();
+/// let mut end = vec!();
+/// r.read_to_end(&mut end).unwrap();
+/// assert_eq!(end, [97, 98, 99]);
+/// ```
+pub struct SeekWith<F, S>
+	where F: ChunkSource<S>,
+	S: AsRef<[u8]>
+{
+	inner: ReadWith<At<F>, Part<S>>,
+}
+
+impl<F, S> SeekWith<F, S>
+	where F: FnMut(u64) -> Option<S>,
+	S: AsRef<[u8]>
+{
+	/// Create an object that will read from the given function.
+	pub fn new(f: F) -> Self
+	{
+		SeekWith::with_source(f)
+	}
+}
+
+impl<F, S, E> SeekWith<TryFn<F>, S>
+	where F: FnMut(u64) -> Result<Option<S>, E>,
+	E: Into<std::io::Error>,
+	S: AsRef<[u8]>
+{
+	/// Create an object that will read from a function that can fail.
+	///
+	/// An error from `f` is returned as by
+	/// [`ReadWith::try_new`](struct.ReadWith.html#method.try_new), and
+	/// `f` is called again at the same offset by the next read.
+	pub fn try_new(f: F) -> Self
+	{
+		SeekWith::with_source(TryFn(f))
+	}
+}
+
+impl<F, S> SeekWith<F, S>
+	where F: ChunkSource<S>,
+	S: AsRef<[u8]>
+{
+	/// Create an object that will read from the given `ChunkSource`,
+	/// with the total length of its `len_hint`.
+	pub fn with_source(f: F) -> Self
+	{
+		let len = f.len_hint();
+		SeekWith
+		{
+			inner: ReadWith::with_generator(At { f, next: 0, len }),
+		}
+	}
+
+	/// Set the total length of the data.
+	///
+	/// Nothing past `len` is read, and `SeekFrom::End` becomes possible.
+	pub fn total_len(mut self, len: u64) -> Self
+	{
+		self.inner.f.len = Some(len);
+		self
+	}
+
+	/// The offset of the next byte to read.
+	fn pos(&self) -> u64
+	{
+		self.inner.f.next - self.inner.buffered().len() as u64
+	}
+}
+
+impl<F, S> Read for SeekWith<F, S>
+	where F: ChunkSource<S>,
+	S: AsRef<[u8]>
+{
+	fn read(&mut self, buf: &mut [u8])
+		-> std::io::Result<usize>
+	{
+		self.inner.read(buf)
+	}
+
+	fn read_vectored(&mut self, bufs: &mut [IoSliceMut])
+		-> std::io::Result<usize>
+	{
+		self.inner.read_vectored(bufs)
+	}
+
+	fn read_to_end(&mut self, buf: &mut Vec<u8>)
+		-> std::io::Result<usize>
+	{
+		self.inner.read_to_end(buf)
+	}
+
+	fn read_to_string(&mut self, buf: &mut String)
+		-> std::io::Result<usize>
+	{
+		self.inner.read_to_string(buf)
+	}
+
+	fn read_exact(&mut self, buf: &mut [u8])
+		-> std::io::Result<()>
+	{
+		self.inner.read_exact(buf)
+	}
+}
+
+impl<F, S> BufRead for SeekWith<F, S>
+	where F: ChunkSource<S>,
+	S: AsRef<[u8]>
+{
+	fn fill_buf(&mut self)
+		-> std::io::Result<&[u8]>
+	{
+		self.inner.fill_buf()
+	}
+
+	fn consume(&mut self, amt: usize)
+	{
+		self.inner.consume(amt);
+	}
+}
+
+impl<F, S> Seek for SeekWith<F, S>
+	where F: ChunkSource<S>,
+	S: AsRef<[u8]>
+{
+	/// Seeking forgets an error that a read has yet to return.
+	fn seek(&mut self, to: SeekFrom)
+		-> std::io::Result<u64>
+	{
+		let (base, delta) = match to
+		{
+			SeekFrom::Start(p) => (p, 0),
+			SeekFrom::Current(d) => (self.pos(), d),
+			SeekFrom::End(d) =>
+			{
+				let len = self.inner.f.len.ok_or_else(
+					|| std::io::Error::new(
+						std::io::ErrorKind::Unsupported,
+						"cannot seek from the end without a total length",
+					)
+				)?;
+				(len, d)
+			},
+		};
+		let pos = base.checked_add_signed(delta)
+			.ok_or_else(
+				|| std::io::Error::new(
+					std::io::ErrorKind::InvalidInput,
+					"invalid seek to a negative or overflowing position",
+				)
+			)?;
+
+		let r = &mut self.inner;
+		r.error = None;
+		// stay in the current chunk if possible
+		let chunk_start = r.f.next - r.current_chunk().len() as u64;
+		if pos >= chunk_start && pos < r.f.next
+		{
+			r.offset = (pos - chunk_start) as usize;
+		}
+		else
+		{
+			r.current = None;
+			r.offset = 0;
+			r.end = false;
+			r.f.next = pos;
+		}
+		Ok(pos)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::{SeekWith, ChunkSource};
+	use std::io::{Read, Seek, SeekFrom, Error, ErrorKind};
+
+	#[test]
+	fn seeking()
+	{
+		let data: Vec<u8> = (0..100).collect();
+		let mut calls = vec!();
+		{
+			let mut r = SeekWith::new(
+				|pos|
+				{
+					calls.push(pos);
+					data.get(pos as usize..).map(|d| &d[..d.len().min(7)])
+				}
+			);
+			let mut buf = [0u8; 5];
+			assert_eq!(r.seek(SeekFrom::Start(10)).unwrap(), 10);
+			r.read_exact(&mut buf).unwrap();
+			assert_eq!(buf, [10, 11, 12, 13, 14]);
+			assert_eq!(r.seek(SeekFrom::Current(-3)).unwrap(), 12);
+			r.read_exact(&mut buf).unwrap();
+			assert_eq!(buf, [12, 13, 14, 15, 16]);
+			assert_eq!(r.seek(SeekFrom::End(0)).unwrap_err().kind(), ErrorKind::Unsupported);
+			assert_eq!(r.seek(SeekFrom::Current(-20)).unwrap_err().kind(), ErrorKind::InvalidInput);
+			assert_eq!(r.seek(SeekFrom::Start(98)).unwrap(), 98);
+			assert_eq!(r.read(&mut buf).unwrap(), 2);
+			assert_eq!(r.read(&mut buf).unwrap(), 0);
+		}
+		assert_eq!(calls, [10, 98, 100]);
+	}
+
+	#[test]
+	fn total_len()
+	{
+		let mut r = SeekWith::new(|pos| Some((pos..pos+4).map(|b| b as u8).collect::<Vec<u8>>()))
+			.total_len(10);
+		let mut all = vec!();
+		r.read_to_end(&mut all).unwrap();
+		assert_eq!(all, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+		assert_eq!(r.seek(SeekFrom::End(-1)).unwrap(), 9);
+		let mut last = vec!();
+		r.read_to_end(&mut last).unwrap();
+		assert_eq!(last, [9]);
+	}
+
+	#[test]
+	fn errors()
+	{
+		let mut failed = false;
+		let mut r = SeekWith::try_new(
+			|pos|
+			{
+				if pos == 4 && !failed
+				{
+					failed = true;
+					return Err(Error::other("failed"));
+				}
+				Ok(Some(vec![pos as u8; 4]))
+			}
+		).total_len(8);
+		let mut buf = [0u8; 8];
+		assert_eq!(r.read(&mut buf).unwrap(), 4);
+		assert_eq!(r.read(&mut buf).unwrap_err().kind(), ErrorKind::Other);
+		assert_eq!(r.read(&mut buf).unwrap(), 4);
+		assert_eq!(&buf[..4], [4, 4, 4, 4]);
+		assert_eq!(r.seek(SeekFrom::Current(-6)).unwrap(), 2);
+		assert_eq!(r.read(&mut buf).unwrap(), 6);
+		assert_eq!(&buf[..6], [2, 2, 2, 2, 6, 6]);
+	}
+
+	/// A file of zeros, one block at a time.
+	struct Zeros(u64);
+
+	impl ChunkSource<Vec<u8>> for Zeros
+	{
+		fn chunk_at(&mut self, pos: u64) -> ::std::io::Result<Option<Vec<u8>>>
+		{
+			Ok(Some(vec![0; (512 - pos % 512) as usize]))
+		}
+
+		fn len_hint(&self) -> Option<u64>
+		{
+			Some(self.0)
+		}
+	}
+
+	#[test]
+	fn source()
+	{
+		let mut r = SeekWith::with_source(Zeros(1000));
+		assert_eq!(r.seek(SeekFrom::End(-10)).unwrap(), 990);
+		let mut rest = vec!();
+		assert_eq!(r.read_to_end(&mut rest).unwrap(), 10);
+	}
+}
@@ -0,0 +1,348 @@
+use std::io::{Read, BufRead};
+use std::time::{Duration, Instant};
+use ::{Generator, ReadWith};
+
+const NANOS: u128 = 1_000_000_000;
+
+/// A source of time for [`Throttled`](struct.Throttled.html)
+///
+/// Tests can implement this with a clock that only
+/// moves forward when `sleep` is called.
+pub trait Clock
+{
+	/// The time since some fixed point, such as the
+	/// creation of the clock.
+	fn now(&self) -> Duration;
+	/// Wait for `d` to pass.
+	fn sleep(&mut self, d: Duration);
+}
+
+/// The `Clock` of the operating system
+pub struct SystemClock
+{
+	start: Instant,
+}
+
+impl SystemClock
+{
+	/// Create a clock that starts now.
+	pub fn new() -> Self
+	{
+		SystemClock { start: Instant::now() }
+	}
+}
+
+impl Default for SystemClock
+{
+	fn default() -> Self
+	{
+		SystemClock::new()
+	}
+}
+
+impl Clock for SystemClock
+{
+	fn now(&self) -> Duration
+	{
+		self.start.elapsed()
+	}
+
+	fn sleep(&mut self, d: Duration)
+	{
+		std::thread::sleep(d);
+	}
+}
+
+/// A limit of `per_sec` bytes or chunks per second, of which
+/// up to `burst` can be read at once after a pause
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate
+{
+	/// How many are allowed each second, on average.
+	pub per_sec: u64,
+	/// How many can be saved up while nothing is read.
+	pub burst: u64,
+}
+
+impl Rate
+{
+	/// A limit of `n` each second, with a burst of as many.
+	pub fn per_sec(n: u64) -> Self
+	{
+		Rate { per_sec: n, burst: n }
+	}
+
+	/// Set how many can be saved up while nothing is read.
+	pub fn burst(mut self, burst: u64) -> Self
+	{
+		self.burst = burst;
+		self
+	}
+}
+
+/// A token bucket, counting in billionths of a token
+/// so that no time is lost to rounding.
+struct Bucket
+{
+	rate: Rate,
+	credit: u128,
+	last: Duration,
+}
+
+impl Bucket
+{
+	fn new(rate: Rate, now: Duration) -> Self
+	{
+		assert!(rate.per_sec > 0 && rate.burst > 0, "rate and burst must not be zero");
+		Bucket { rate, credit: rate.burst as u128 * NANOS, last: now }
+	}
+
+	fn refill(&mut self, now: Duration)
+	{
+		let elapsed = now.saturating_sub(self.last).as_nanos();
+		self.last = now.max(self.last);
+		self.credit = (self.credit + elapsed * self.rate.per_sec as u128)
+			.min(self.rate.burst as u128 * NANOS);
+	}
+
+	/// Wait until `n` tokens are available, which is at most the burst.
+	fn wait<C: Clock>(&mut self, clock: &mut C, n: u64)
+	{
+		let needed = n.min(self.rate.burst) as u128 * NANOS;
+		loop
+		{
+			self.refill(clock.now());
+			if self.credit >= needed
+				{ return; }
+			let per_sec = self.rate.per_sec as u128;
+			let nanos = (needed - self.credit).div_ceil(per_sec);
+			clock.sleep(Duration::from_nanos(nanos as u64));
+		}
+	}
+
+	fn available(&self) -> u64
+	{
+		(self.credit / NANOS) as u64
+	}
+
+	fn take(&mut self, n: u64)
+	{
+		self.credit = self.credit.saturating_sub(n as u128 * NANOS);
+	}
+}
+
+/// A reader that limits how fast a `ReadWith` is read
+///
+/// Created by [`ReadWith::throttle`](struct.ReadWith.html#method.throttle).
+/// Each call to `read` returns bytes from at most one chunk, and
+/// sleeps first if the limits have been reached.
+///
+/// ```rust
+/// use std::io::Read;
+/// use read_with::{ReadWith, Rate};
+/// let mut r = ReadWith::from_iter(vec!["one", "two", "three"])
+///     .throttle()
+///     .bytes(Rate::per_sec(1_000_000))
+///     .chunks(Rate::per_sec(1000));
+/// let mut s = String::new();
+/// r.read_to_string(&mut s).unwrap();
+/// assert_eq!(s, "onetwothree");
+/// ```
+pub struct Throttled<F, S, C>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	inner: ReadWith<F, S>,
+	clock: C,
+	bytes: Option<Bucket>,
+	chunks: Option<Bucket>,
+}
+
+impl<F, S> ReadWith<F, S>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	/// Limit how fast this is read, as measured by the system clock.
+	///
+	/// Without [`bytes`](struct.Throttled.html#method.bytes) or
+	/// [`chunks`](struct.Throttled.html#method.chunks), there is no limit.
+	pub fn throttle(self) -> Throttled<F, S, SystemClock>
+	{
+		self.throttle_with_clock(SystemClock::new())
+	}
+
+	/// Limit how fast this is read, as measured by `clock`.
+	pub fn throttle_with_clock<C>(self, clock: C) -> Throttled<F, S, C>
+		where C: Clock
+	{
+		Throttled
+		{
+			inner: self,
+			clock,
+			bytes: None,
+			chunks: None,
+		}
+	}
+}
+
+impl<F, S, C> Throttled<F, S, C>
+	where F: Generator<S>,
+	S: AsRef<[u8]>,
+	C: Clock
+{
+	/// Limit the bytes read.
+	///
+	/// Panics if the rate or the burst is zero.
+	pub fn bytes(mut self, rate: Rate) -> Self
+	{
+		self.bytes = Some(Bucket::new(rate, self.clock.now()));
+		self
+	}
+
+	/// Limit the calls to the generator, each of which counts as
+	/// a chunk, even if it returns an empty one or the end.
+	///
+	/// Panics if the rate or the burst is zero.
+	pub fn chunks(mut self, rate: Rate) -> Self
+	{
+		self.chunks = Some(Bucket::new(rate, self.clock.now()));
+		self
+	}
+
+	/// The clock that measures the rate.
+	pub fn clock(&self) -> &C
+	{
+		&self.clock
+	}
+
+	/// Remove the limits, returning the reader.
+	pub fn into_inner(self) -> ReadWith<F, S>
+	{
+		self.inner
+	}
+}
+
+impl<F, S, C> Read for Throttled<F, S, C>
+	where F: Generator<S>,
+	S: AsRef<[u8]>,
+	C: Clock
+{
+	fn read(&mut self, buf: &mut [u8])
+		-> std::io::Result<usize>
+	{
+		if buf.is_empty()
+			{ return Ok(0); }
+
+		if let Some(ref mut chunks) = self.chunks
+		{
+			// every call to the generator takes a token, even
+			// one that returns an empty chunk or the end
+			while self.inner.needs_chunk()
+			{
+				chunks.wait(&mut self.clock, 1);
+				chunks.take(1);
+				self.inner.generate_chunk()?;
+			}
+		}
+		let available = self.inner.fill_buf()?.len();
+		if available == 0
+			{ return Ok(0); }
+
+		let mut count = buf.len().min(available);
+		if let Some(ref mut bytes) = self.bytes
+		{
+			bytes.wait(&mut self.clock, count as u64);
+			count = count.min(bytes.available() as usize);
+			bytes.take(count as u64);
+		}
+		let next = self.inner.fill_buf()?;
+		buf[..count].copy_from_slice(&next[..count]);
+		self.inner.consume(count);
+		Ok(count)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::{ReadWith, Clock, Rate};
+	use std::io::Read;
+	use std::cell::Cell;
+	use std::rc::Rc;
+	use std::time::Duration;
+
+	/// Only moves when something sleeps.
+	#[derive(Clone, Default)]
+	struct FakeClock(Rc<Cell<Duration>>);
+
+	impl Clock for FakeClock
+	{
+		fn now(&self) -> Duration
+		{
+			self.0.get()
+		}
+
+		fn sleep(&mut self, d: Duration)
+		{
+			self.0.set(self.0.get() + d);
+		}
+	}
+
+	#[test]
+	fn bytes()
+	{
+		let clock = FakeClock::default();
+		let mut r = ReadWith::from_iter(vec![vec![7u8; 2500]])
+			.throttle_with_clock(clock.clone())
+			.bytes(Rate::per_sec(1000).burst(500));
+		let mut buf = [0u8; 800];
+		assert_eq!(r.read(&mut buf).unwrap(), 500);
+		assert_eq!(clock.now(), Duration::from_secs(0));
+		assert_eq!(r.read(&mut buf).unwrap(), 500);
+		assert_eq!(clock.now(), Duration::from_millis(500));
+
+		// time passing while nothing is read can only be saved up to the burst
+		clock.0.set(Duration::from_secs(10));
+		assert_eq!(r.read(&mut buf).unwrap(), 500);
+		let mut rest = vec!();
+		r.read_to_end(&mut rest).unwrap();
+		assert_eq!(rest.len(), 1000);
+		assert_eq!(clock.now(), Duration::from_secs(11));
+	}
+
+	#[test]
+	fn chunks()
+	{
+		let clock = FakeClock::default();
+		let mut r = ReadWith::from_iter(vec!["a", "", "bc", "d", "ef"])
+			.throttle_with_clock(clock.clone())
+			.chunks(Rate::per_sec(4).burst(2));
+		let mut s = String::new();
+		r.read_to_string(&mut s).unwrap();
+		assert_eq!(s, "abcdef");
+		// six calls, counting the empty chunk and the end:
+		// two at once, then one every 250ms
+		assert_eq!(clock.now(), Duration::from_millis(1000));
+	}
+
+	#[test]
+	fn empty_chunks()
+	{
+		let clock = FakeClock::default();
+		let mut calls = 0;
+		let mut r = ReadWith::new(
+			||
+			{
+				calls += 1;
+				if calls < 999 { Some("") } else { None }
+			}
+		)
+			.throttle_with_clock(clock.clone())
+			.chunks(Rate::per_sec(1));
+		let mut buf = [0u8; 4];
+		assert_eq!(r.read(&mut buf).unwrap(), 0);
+		drop(r);
+		assert_eq!(calls, 999);
+		assert_eq!(clock.now(), Duration::from_secs(998));
+	}
+}
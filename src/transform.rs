@@ -0,0 +1,266 @@
+use std::marker::PhantomData;
+use ::{Generator, ReadWith};
+
+/// A streaming transformation of the data, such as a
+/// change of encoding
+///
+/// Each call may hold back bytes, for example the start of
+/// a sequence that continues in the next chunk, and output
+/// them later. Implemented for every
+/// `FnMut(&[u8], &mut Vec<u8>) -> io::Result<()>`.
+pub trait Transform
+{
+	/// Transform `input`, appending the result to `out`.
+	fn transform(&mut self, input: &[u8], out: &mut Vec<u8>)
+		-> std::io::Result<()>;
+
+	/// Append whatever was held back, at the end of the data.
+	fn finish(&mut self, _out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		Ok(())
+	}
+}
+
+impl<F> Transform for F
+	where F: FnMut(&[u8], &mut Vec<u8>) -> std::io::Result<()>
+{
+	fn transform(&mut self, input: &[u8], out: &mut Vec<u8>)
+		-> std::io::Result<()>
+	{
+		self(input, out)
+	}
+}
+
+/// A `Generator` that applies a `Transform` to another
+///
+/// Created by [`ReadWith::transform`](struct.ReadWith.html#method.transform).
+pub struct Transformed<F, S, T>
+{
+	f: F,
+	t: T,
+	finished: bool,
+	chunk: PhantomData<fn() -> S>,
+}
+
+impl<F, S, T> Generator<Vec<u8>> for Transformed<F, S, T>
+	where F: Generator<S>,
+	S: AsRef<[u8]>,
+	T: Transform
+{
+	fn generate(&mut self) -> std::io::Result<Option<Vec<u8>>>
+	{
+		while !self.finished
+		{
+			let mut out = vec!();
+			match self.f.generate()?
+			{
+				Some(chunk) => self.t.transform(chunk.as_ref(), &mut out)?,
+				None =>
+				{
+					self.finished = true;
+					self.t.finish(&mut out)?;
+				},
+			}
+			if !out.is_empty()
+				{ return Ok(Some(out)); }
+		}
+		Ok(None)
+	}
+}
+
+/// A `Generator` that maps the chunks of another
+///
+/// Created by [`ReadWith::map_chunks`](struct.ReadWith.html#method.map_chunks).
+pub struct MapChunks<F, M, S>
+{
+	f: F,
+	m: M,
+	chunk: PhantomData<fn() -> S>,
+}
+
+impl<F, M, S, T> Generator<T> for MapChunks<F, M, S>
+	where F: Generator<S>,
+	M: FnMut(S) -> T
+{
+	fn generate(&mut self) -> std::io::Result<Option<T>>
+	{
+		Ok(self.f.generate()?.map(&mut self.m))
+	}
+}
+
+/// A `Generator` that skips some chunks of another
+///
+/// Created by [`ReadWith::filter_chunks`](struct.ReadWith.html#method.filter_chunks).
+pub struct FilterChunks<F, P>
+{
+	f: F,
+	p: P,
+}
+
+impl<F, P, S> Generator<S> for FilterChunks<F, P>
+	where F: Generator<S>,
+	P: FnMut(&S) -> bool
+{
+	fn generate(&mut self) -> std::io::Result<Option<S>>
+	{
+		while let Some(c) = self.f.generate()?
+		{
+			if (self.p)(&c)
+				{ return Ok(Some(c)); }
+		}
+		Ok(None)
+	}
+}
+
+/// A `Generator` that shows each chunk of another to a function
+///
+/// Created by [`ReadWith::inspect`](struct.ReadWith.html#method.inspect).
+pub struct Inspect<F, I>
+{
+	f: F,
+	i: I,
+}
+
+impl<F, I, S> Generator<S> for Inspect<F, I>
+	where F: Generator<S>,
+	I: FnMut(&S)
+{
+	fn generate(&mut self) -> std::io::Result<Option<S>>
+	{
+		let c = self.f.generate()?;
+		if let Some(ref c) = c
+			{ (self.i)(c); }
+		Ok(c)
+	}
+}
+
+/// These are meant to be called before reading; if some data
+/// was already read, the rest of the current chunk is read first,
+/// unchanged. The [`total_len`](#method.total_len) is not kept.
+impl<F, S> ReadWith<F, S>
+	where F: Generator<S>,
+	S: AsRef<[u8]>
+{
+	/// Read the result of `m` on each chunk.
+	///
+	/// ```rust
+	/// use std::io::Read;
+	/// let mut s = String::new();
+	/// read_with::ReadWith::from_iter(vec!["one", "two"])
+	///     .map_chunks(|c| c.to_uppercase())
+	///     .read_to_string(&mut s)
+	///     .unwrap();
+	/// assert_eq!(s, "ONETWO");
+	/// ```
+	pub fn map_chunks<M, T>(self, m: M) -> ReadWith<MapChunks<F, M, S>, T>
+		where M: FnMut(S) -> T,
+		T: AsRef<[u8]>
+	{
+		self.replace_generator(|f| MapChunks { f, m, chunk: PhantomData })
+	}
+
+	/// Only read the chunks for which `p` returns true.
+	pub fn filter_chunks<P>(self, p: P) -> ReadWith<FilterChunks<F, P>, S>
+		where P: FnMut(&S) -> bool
+	{
+		self.replace_generator(|f| FilterChunks { f, p })
+	}
+
+	/// Call `i` with each chunk as it is generated.
+	pub fn inspect<I>(self, i: I) -> ReadWith<Inspect<F, I>, S>
+		where I: FnMut(&S)
+	{
+		self.replace_generator(|f| Inspect { f, i })
+	}
+
+	/// Read the data as transformed by `t`.
+	pub fn transform<T>(self, t: T) -> ReadWith<Transformed<F, S, T>, Vec<u8>>
+		where T: Transform
+	{
+		self.replace_generator(|f| Transformed { f, t, finished: false, chunk: PhantomData })
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::{ReadWith, Transform};
+	use std::io::Read;
+
+	/// Replaces `\r\n` with `\n`, even when split between chunks.
+	#[derive(Default)]
+	struct Crlf
+	{
+		cr: bool,
+	}
+
+	impl Transform for Crlf
+	{
+		fn transform(&mut self, input: &[u8], out: &mut Vec<u8>)
+			-> ::std::io::Result<()>
+		{
+			for &b in input
+			{
+				if self.cr && b != b'\n'
+					{ out.push(b'\r'); }
+				self.cr = b == b'\r';
+				if !self.cr
+					{ out.push(b); }
+			}
+			Ok(())
+		}
+
+		fn finish(&mut self, out: &mut Vec<u8>)
+			-> ::std::io::Result<()>
+		{
+			if self.cr
+				{ out.push(b'\r'); }
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn transforms()
+	{
+		let mut s = String::new();
+		ReadWith::from_iter(vec!["one\r", "\ntwo\r\n\r", "\r", "x\r"])
+			.transform(Crlf::default())
+			.read_to_string(&mut s)
+			.unwrap();
+		assert_eq!(s, "one\ntwo\n\r\rx\r");
+
+		s.clear();
+		ReadWith::from_iter(vec!["one", "two"])
+			.transform(
+				|i: &[u8], o: &mut Vec<u8>|
+				{
+					o.extend(i.iter().map(u8::to_ascii_uppercase));
+					Ok(())
+				}
+			)
+			.read_to_string(&mut s)
+			.unwrap();
+		assert_eq!(s, "ONETWO");
+	}
+
+	#[test]
+	fn combinators()
+	{
+		let mut seen = vec!();
+		let mut s = String::new();
+		{
+			let mut r = ReadWith::from_iter(vec!["one", "", "two", "skip", "three"]);
+			let mut buf = [0u8; 2];
+			r.read_exact(&mut buf).unwrap();
+			assert_eq!(&buf, b"on");
+			r.filter_chunks(|c| !c.is_empty() && *c != "skip")
+				.inspect(|c| seen.push(c.len()))
+				.map_chunks(|c| c.to_string() + ";")
+				.read_to_string(&mut s)
+				.unwrap();
+		}
+		assert_eq!(s, "etwo;three;");
+		assert_eq!(seen, [3, 5]);
+	}
+}
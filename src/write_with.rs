@@ -0,0 +1,240 @@
+use std::io::Write;
+
+/// How a [`WriteWith`](struct.WriteWith.html) divides the written
+/// data before giving it to its function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chunking
+{
+	/// Each call to `write` is given to the function as-is.
+	PassThrough,
+	/// The function receives blocks of exactly this many bytes,
+	/// except possibly the last one, which is given on `flush`.
+	Blocks(usize),
+	/// The function receives one line at a time, including its `\n`.
+	/// An unterminated last line is given on `flush`.
+	Lines,
+}
+
+impl Chunking
+{
+	/// How many bytes of `input` complete the next chunk, given
+	/// that `pending` bytes are already waiting.
+	fn boundary(&self, pending: usize, input: &[u8]) -> Option<usize>
+	{
+		match *self
+		{
+			Chunking::PassThrough => Some(input.len()),
+			Chunking::Blocks(n) =>
+			{
+				let want = n - pending;
+				if input.len() >= want
+					{ Some(want) }
+				else
+					{ None }
+			},
+			Chunking::Lines =>
+				input.iter().position(|&b| b == b'\n').map(|p| p+1),
+		}
+	}
+}
+
+fn no_flush() -> std::io::Result<()>
+{
+	Ok(())
+}
+
+/// An object that implements the `Write` trait
+///
+/// The written data is given to a function, optionally
+/// divided into blocks or lines.
+///
+/// Example:
+///
+/// ```rust
+/// use std::io::Write;
+/// let mut lines = vec!();
+/// {
+///     let mut w = read_with::WriteWith::new(
+///         |line: &[u8]|
+///         {
+///             lines.push(String::from_utf8_lossy(line).into_owned());
+///             Ok(())
+///         }
+///     ).chunking(read_with::Chunking::Lines);
+///     write!(w, "one\ntw").unwrap();
+///     write!(w, "o\nthree").unwrap();
+///     w.flush().unwrap();
+/// }
+/// assert_eq!(lines, ["one\n", "two\n", "three"]);
+/// ```
+pub struct WriteWith<F, G=fn() -> std::io::Result<()>>
+	where F: FnMut(&[u8]) -> std::io::Result<()>,
+	G: FnMut() -> std::io::Result<()>
+{
+	f: F,
+	flush: G,
+	chunking: Chunking,
+	pending: Vec<u8>,
+}
+
+impl<F> WriteWith<F>
+	where F: FnMut(&[u8]) -> std::io::Result<()>
+{
+	/// Create an object that will write to the given function.
+	///
+	/// By default every `write` is passed straight through to `f`.
+	pub fn new(f: F) -> Self
+	{
+		WriteWith::with_flush(f, no_flush)
+	}
+}
+
+impl<F, G> WriteWith<F, G>
+	where F: FnMut(&[u8]) -> std::io::Result<()>,
+	G: FnMut() -> std::io::Result<()>
+{
+	/// Create an object that will write to `f` and call `flush`
+	/// when it is flushed, after any buffered data has been given to `f`.
+	pub fn with_flush(f: F, flush: G) -> Self
+	{
+		WriteWith
+		{
+			f,
+			flush,
+			chunking: Chunking::PassThrough,
+			pending: vec!(),
+		}
+	}
+
+	/// Set how the data is divided before being given to the function.
+	///
+	/// Panics if given `Chunking::Blocks(0)`.
+	pub fn chunking(mut self, chunking: Chunking) -> Self
+	{
+		if let Chunking::Blocks(n) = chunking
+			{ assert!(n > 0, "block size must not be zero"); }
+		self.chunking = chunking;
+		self
+	}
+
+	fn write_pending(&mut self) -> std::io::Result<()>
+	{
+		if !self.pending.is_empty()
+		{
+			(self.f)(&self.pending)?;
+			self.pending.clear();
+		}
+		Ok(())
+	}
+}
+
+impl<F, G> Write for WriteWith<F, G>
+	where F: FnMut(&[u8]) -> std::io::Result<()>,
+	G: FnMut() -> std::io::Result<()>
+{
+	fn write(&mut self, buf: &[u8])
+		-> std::io::Result<usize>
+	{
+		let mut used = 0;
+		while used < buf.len()
+		{
+			let rest = &buf[used..];
+			let take = match self.chunking.boundary(self.pending.len(), rest)
+			{
+				Some(take) => take,
+				None =>
+				{
+					self.pending.extend_from_slice(rest);
+					used = buf.len();
+					break;
+				},
+			};
+
+			let r = if self.pending.is_empty()
+			{
+				(self.f)(&rest[..take])
+			}
+			else
+			{
+				let before = self.pending.len();
+				self.pending.extend_from_slice(&rest[..take]);
+				let r = (self.f)(&self.pending);
+				if r.is_ok()
+					{ self.pending.clear(); }
+				else
+					{ self.pending.truncate(before); }
+				r
+			};
+
+			match r
+			{
+				Ok(()) => used += take,
+				Err(e) =>
+				{
+					if used == 0
+						{ return Err(e); }
+					break;
+				},
+			}
+		}
+
+		Ok(used)
+	}
+
+	fn flush(&mut self)
+		-> std::io::Result<()>
+	{
+		self.write_pending()?;
+		(self.flush)()
+	}
+}
+
+impl<F, G> Drop for WriteWith<F, G>
+	where F: FnMut(&[u8]) -> std::io::Result<()>,
+	G: FnMut() -> std::io::Result<()>
+{
+	fn drop(&mut self)
+	{
+		let _ = self.write_pending();
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use ::{WriteWith, Chunking};
+	use std::io::Write;
+
+	#[test]
+	fn blocks()
+	{
+		let mut blocks = vec!();
+		let mut flushed = 0;
+		{
+			let mut w = WriteWith::with_flush(
+				|b: &[u8]| { blocks.push(b.to_vec()); Ok(()) },
+				|| { flushed += 1; Ok(()) },
+			).chunking(Chunking::Blocks(3));
+			w.write_all(b"ab").unwrap();
+			w.write_all(b"cdefg").unwrap();
+			w.write_all(b"h").unwrap();
+			w.flush().unwrap();
+			w.write_all(b"ij").unwrap();
+		}
+		assert_eq!(blocks, [&b"abc"[..], b"def", b"gh", b"ij"]);
+		assert_eq!(flushed, 1);
+	}
+
+	#[test]
+	fn lines()
+	{
+		let mut output = vec!();
+		{
+			let mut w = WriteWith::new(
+				|l: &[u8]| { output.push(String::from_utf8(l.to_vec()).unwrap()); Ok(()) }
+			).chunking(Chunking::Lines);
+			::std::io::copy(&mut &b"one\ntwo\n\nthree"[..], &mut w).unwrap();
+		}
+		assert_eq!(output, ["one\n", "two\n", "\n", "three"]);
+	}
+}